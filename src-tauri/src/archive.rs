@@ -0,0 +1,324 @@
+use crate::blobs;
+use crate::extract::PageContent;
+use crate::fetch::{FetchedPage, PageFetcher};
+use crate::settings::SettingsManager;
+use crate::AppState;
+use rusqlite::{params, Connection};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, State};
+use tauri_plugin_opener::OpenerExt;
+use url::Url;
+
+const ARCHIVE_DIR: &str = "archive";
+const IMAGES_DIR: &str = "images";
+
+/// Which URL clips get an offline copy, from the `archive_*` settings
+#[derive(Debug, Clone)]
+pub struct ArchiveRules {
+    pub categories: Vec<String>,
+    /// a domain covers its subdomains too
+    pub domains: Vec<String>,
+    pub max_images: usize,
+}
+
+impl ArchiveRules {
+    pub fn from_settings(settings: &SettingsManager) -> Self {
+        let list = |key: &str| -> Vec<String> {
+            let json = settings.get_setting(key).unwrap_or_default();
+            match serde_json::from_str::<Vec<String>>(&json) {
+                Ok(values) => values
+                    .iter()
+                    .map(|value| value.trim().trim_start_matches('.').to_lowercase())
+                    .filter(|value| !value.is_empty())
+                    .collect(),
+                Err(e) => {
+                    if !json.trim().is_empty() {
+                        eprintln!("Invalid {} setting: {}", key, e);
+                    }
+                    Vec::new()
+                }
+            }
+        };
+
+        Self {
+            categories: list("archive_categories"),
+            domains: list("archive_domains"),
+            max_images: settings
+                .get_setting("archive_max_images")
+                .and_then(|value| value.trim().parse::<usize>().ok())
+                .unwrap_or(5),
+        }
+    }
+
+    pub fn matches(&self, url: &str, category: Option<&str>) -> bool {
+        let category_matches = category.is_some_and(|category| {
+            self.categories
+                .iter()
+                .any(|wanted| wanted == &category.to_lowercase())
+        });
+
+        let host = Url::parse(url.trim())
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_lowercase));
+        let domain_matches = host.is_some_and(|host| {
+            self.domains.iter().any(|domain| {
+                host == *domain
+                    || host
+                        .strip_suffix(domain.as_str())
+                        .is_some_and(|rest| rest.ends_with('.'))
+            })
+        });
+
+        category_matches || domain_matches
+    }
+}
+
+/// Where a clip's archived page lives in the blob store
+pub fn archive_dir(db_path: &Path, clip_id: i64) -> PathBuf {
+    blobs::clip_blob_dir(db_path, clip_id).join(ARCHIVE_DIR)
+}
+
+/// Save a readable copy of a fetched page as `index.html` and `page.md`, with up to
+/// `max_images` of its images next to them. Replaces an earlier archive of the clip.
+/// Returns how many images were saved
+pub async fn archive_page(
+    db_path: &Path,
+    clip_id: i64,
+    fetcher: &PageFetcher,
+    page: &FetchedPage,
+    content: &PageContent,
+    max_images: usize,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let base = Url::parse(&page.final_url)?;
+    // written next to the live archive and swapped in once complete, so a cancelled or
+    // failed run never leaves a half written archive behind
+    let archive = archive_dir(db_path, clip_id);
+    let dir = archive.with_extension("partial");
+    if dir.exists() {
+        fs::remove_dir_all(&dir)?;
+    }
+    fs::create_dir_all(dir.join(IMAGES_DIR))?;
+
+    // key images only: the preview image and the first few in the text
+    let mut images = Vec::new();
+    let mut seen = Vec::new();
+    for image in &content.images {
+        if images.len() >= max_images {
+            break;
+        }
+        // the preview image is often in the text as well, under a relative path
+        let Ok(url) = base.join(&image.src) else {
+            continue;
+        };
+        if !matches!(url.scheme(), "http" | "https") || seen.contains(&url) {
+            continue;
+        }
+        seen.push(url.clone());
+
+        let fetched = match fetcher.fetch_image(url.as_str()).await {
+            Ok(fetched) => fetched,
+            Err(e) => {
+                eprintln!("Skipping image {} for clip {}: {}", url, clip_id, e);
+                continue;
+            }
+        };
+        // svg can carry scripts, only plain raster images are kept
+        let Some(extension) = image_extension(&fetched.content_type) else {
+            continue;
+        };
+
+        let file = format!("{}/{}.{}", IMAGES_DIR, images.len() + 1, extension);
+        fs::write(dir.join(&file), &fetched.bytes)?;
+        images.push((file, image.alt.clone()));
+    }
+
+    let title = content
+        .title
+        .clone()
+        .unwrap_or_else(|| page.final_url.clone());
+    fs::write(
+        dir.join("page.md"),
+        to_markdown(&title, page, content, &images),
+    )?;
+    fs::write(
+        dir.join("index.html"),
+        to_html(&title, page, content, &images),
+    )?;
+    replace_dir(&dir, &archive)?;
+
+    Connection::open(db_path)?.execute(
+        "UPDATE link_pages SET archived_at = CURRENT_TIMESTAMP WHERE clip_id = ?",
+        params![clip_id],
+    )?;
+
+    Ok(images.len())
+}
+
+// a directory can't be renamed over a non-empty one, so the old archive is moved aside first
+fn replace_dir(new: &Path, target: &Path) -> std::io::Result<()> {
+    let old = target.with_extension("old");
+    if old.exists() {
+        fs::remove_dir_all(&old)?;
+    }
+    if target.exists() {
+        fs::rename(target, &old)?;
+    }
+    fs::rename(new, target)?;
+    if old.exists() {
+        fs::remove_dir_all(&old)?;
+    }
+    Ok(())
+}
+
+fn image_extension(content_type: &str) -> Option<&'static str> {
+    let mime = content_type.split(';').next().unwrap_or_default().trim();
+    match mime {
+        "image/png" => Some("png"),
+        "image/jpeg" | "image/jpg" => Some("jpg"),
+        "image/gif" => Some("gif"),
+        "image/webp" => Some("webp"),
+        "image/avif" => Some("avif"),
+        _ => None,
+    }
+}
+
+fn to_markdown(
+    title: &str,
+    page: &FetchedPage,
+    content: &PageContent,
+    images: &[(String, Option<String>)],
+) -> String {
+    let mut out = format!("# {}\n\nSource: <{}>\n", title, page.final_url);
+    if let Some(description) = &content.description {
+        out.push_str(&format!("\n> {}\n", description));
+    }
+    if page.truncated {
+        out.push_str("\n*Only the beginning of the page was saved.*\n");
+    }
+
+    // one block per line in the extracted text, paragraphs need a blank line between them
+    let mut in_list = false;
+    out.push('\n');
+    for line in content.text.lines() {
+        let item = line.starts_with("- ");
+        if in_list && !item {
+            out.push('\n');
+        }
+        in_list = item;
+        out.push_str(line);
+        out.push_str(if item { "\n" } else { "\n\n" });
+    }
+
+    for (file, alt) in images {
+        out.push_str(&format!(
+            "\n![{}]({})\n",
+            alt.as_deref().unwrap_or(""),
+            file
+        ));
+    }
+    out
+}
+
+fn to_html(
+    title: &str,
+    page: &FetchedPage,
+    content: &PageContent,
+    images: &[(String, Option<String>)],
+) -> String {
+    let mut body = String::new();
+    let mut in_list = false;
+
+    for line in content.text.lines() {
+        let item = line.strip_prefix("- ");
+        if in_list && item.is_none() {
+            body.push_str("</ul>\n");
+        }
+        if !in_list && item.is_some() {
+            body.push_str("<ul>\n");
+        }
+        in_list = item.is_some();
+
+        let level = line.chars().take_while(|c| *c == '#').count();
+        if let Some(item) = item {
+            body.push_str(&format!("<li>{}</li>\n", escape(item)));
+        } else if (1..=6).contains(&level) && line[level..].starts_with(' ') {
+            body.push_str(&format!(
+                "<h{0}>{1}</h{0}>\n",
+                level,
+                escape(line[level..].trim())
+            ));
+        } else {
+            body.push_str(&format!("<p>{}</p>\n", escape(line)));
+        }
+    }
+    if in_list {
+        body.push_str("</ul>\n");
+    }
+
+    for (file, alt) in images {
+        body.push_str(&format!(
+            "<figure><img src=\"{}\" alt=\"{}\"></figure>\n",
+            escape(file),
+            escape(alt.as_deref().unwrap_or(""))
+        ));
+    }
+
+    let description = content
+        .description
+        .as_deref()
+        .map(|description| format!("<blockquote>{}</blockquote>\n", escape(description)))
+        .unwrap_or_default();
+    let truncated = if page.truncated {
+        "<p><em>Only the beginning of the page was saved.</em></p>\n"
+    } else {
+        ""
+    };
+
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+<style>body {{ max-width: 42rem; margin: 2rem auto; padding: 0 1rem; font: 16px/1.6 sans-serif; }} img {{ max-width: 100%; }} .source {{ color: #666; }}</style>
+</head>
+<body>
+<h1>{title}</h1>
+<p class="source">Archived from <a href="{url}">{url}</a></p>
+{description}{truncated}{body}</body>
+</html>
+"#,
+        title = escape(title),
+        url = escape(&page.final_url),
+    )
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Open a clip's archived page in the default browser
+#[tauri::command]
+pub fn open_archive(
+    app_handle: AppHandle,
+    state: State<'_, AppState>,
+    item_id: String,
+) -> Result<(), String> {
+    let clip_id = item_id
+        .parse::<i64>()
+        .map_err(|_| "Invalid item id".to_string())?;
+
+    let index = archive_dir(&state.db_path, clip_id).join("index.html");
+    if !index.exists() {
+        return Err("This item has no archived copy".to_string());
+    }
+
+    app_handle
+        .opener()
+        .open_path(index.to_string_lossy(), None::<&str>)
+        .map_err(|e| format!("Failed to open archive: {e}"))
+}
@@ -0,0 +1,59 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Root of the blob store, kept next to the database in the app data directory
+pub fn blob_root(db_path: &Path) -> PathBuf {
+    db_path
+        .parent()
+        .map(|dir| dir.join("blobs"))
+        .unwrap_or_else(|| PathBuf::from("blobs"))
+}
+
+/// Directory holding every file that belongs to a single clip
+pub fn clip_blob_dir(db_path: &Path, clip_id: i64) -> PathBuf {
+    blob_root(db_path).join(clip_id.to_string())
+}
+
+/// Overwrite and remove every blob file stored for a clip
+pub fn remove_clip_blobs(db_path: &Path, clip_id: i64) -> io::Result<()> {
+    let dir = clip_blob_dir(db_path, clip_id);
+
+    if !dir.exists() {
+        return Ok(());
+    }
+
+    shred_dir(&dir)?;
+    fs::remove_dir_all(&dir)
+}
+
+fn shred_dir(dir: &Path) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            shred_dir(&path)?;
+        } else {
+            shred_file(&path)?;
+        }
+    }
+
+    Ok(())
+}
+
+// zero the contents before unlinking so the data doesn't linger in free blocks
+fn shred_file(path: &Path) -> io::Result<()> {
+    let len = fs::metadata(path)?.len() as usize;
+    let mut file = OpenOptions::new().write(true).open(path)?;
+
+    let zeros = vec![0u8; len.min(64 * 1024)];
+    let mut remaining = len;
+    while remaining > 0 {
+        let chunk = remaining.min(zeros.len());
+        file.write_all(&zeros[..chunk])?;
+        remaining -= chunk;
+    }
+    file.sync_all()?;
+
+    fs::remove_file(path)
+}
@@ -1,3 +1,10 @@
+use crate::blobs;
+use crate::expiry;
+use crate::extract::LinkMetadata;
+use crate::fetch::{self, LinkPage};
+use crate::library;
+use crate::link_health::{self, LinkHealth};
+use crate::llm::{self, ClipAnalysis};
 use crate::shortcut::{save_clip, Clip};
 use crate::AppState;
 use rusqlite::{params, Connection};
@@ -9,10 +16,25 @@ use tauri::{Emitter, Manager, State};
 pub struct ClipItem {
     pub id: String,
     pub clip: Clip,
+    pub title: Option<String>,
     pub category: Option<String>,
     pub summary: Option<String>,
     pub tags: Option<Vec<String>>,
     pub created_at: String,
+    pub expires_at: Option<String>,
+    pub sensitive: bool,
+    /// pending | done | offline | failed | cancelled | skipped
+    pub ai_status: String,
+    /// fields the user set by hand, AI results leave these alone
+    pub manual_fields: Vec<String>,
+    /// the fetched page behind a URL clip
+    pub link: Option<LinkPage>,
+    /// whether a URL clip's link still works, from the last periodic check
+    pub link_health: Option<LinkHealth>,
+    /// a URL clip's link without tracking parameters and such, clips of the same page share it
+    pub canonical_url: Option<String>,
+    /// title, description, site name, favicon and image for a link preview card
+    pub preview: Option<LinkMetadata>,
 }
 
 #[tauri::command]
@@ -29,8 +51,34 @@ pub async fn get_items(state: State<'_, AppState>) -> Result<Vec<ClipItem>, Stri
           created_at,
           category,
           summary,
-          tags
+          tags,
+          expires_at,
+          sensitive,
+          clips.title,
+          ai_status,
+          manual_fields,
+          link_pages.final_url,
+          link_pages.title,
+          link_pages.description,
+          link_pages.fetched_at,
+          link_pages.error,
+          link_pages.archived_at,
+          link_health.state,
+          link_health.status_code,
+          link_health.redirect_url,
+          link_health.error,
+          link_health.checked_at,
+          canonical_url,
+          link_metadata.clip_id,
+          link_metadata.title,
+          link_metadata.description,
+          link_metadata.site_name,
+          link_metadata.favicon_url,
+          link_metadata.image_url
         FROM clips
+        LEFT JOIN link_pages ON link_pages.clip_id = clips.id
+        LEFT JOIN link_health ON link_health.clip_id = clips.id
+        LEFT JOIN link_metadata ON link_metadata.clip_id = clips.id
         ORDER BY created_at DESC
         "#,
         )
@@ -44,6 +92,48 @@ pub async fn get_items(state: State<'_, AppState>) -> Result<Vec<ClipItem>, Stri
             let category: Option<String> = row.get(3).ok();
             let summary: Option<String> = row.get(4).ok();
             let tags_json: Option<String> = row.get(5).ok();
+            let expires_at: Option<String> = row.get(6).ok().flatten();
+            let sensitive: bool = row.get(7).unwrap_or(false);
+            let title: Option<String> = row.get(8).ok().flatten();
+            let ai_status: String = row.get(9)?;
+            let manual_fields: Vec<String> =
+                serde_json::from_str(&row.get::<_, String>(10)?).unwrap_or_default();
+            let link = row
+                .get::<_, Option<String>>(14)?
+                .map(|fetched_at| -> rusqlite::Result<LinkPage> {
+                    Ok(LinkPage {
+                        final_url: row.get(11)?,
+                        title: row.get(12)?,
+                        description: row.get(13)?,
+                        fetched_at,
+                        error: row.get(15)?,
+                        archived_at: row.get(16)?,
+                    })
+                })
+                .transpose()?;
+            let link_health = row
+                .get::<_, Option<String>>(17)?
+                .map(|state| -> rusqlite::Result<LinkHealth> {
+                    Ok(LinkHealth {
+                        state,
+                        status_code: row.get(18)?,
+                        redirect_url: row.get(19)?,
+                        error: row.get(20)?,
+                        checked_at: row.get(21)?,
+                    })
+                })
+                .transpose()?;
+            let canonical_url: Option<String> = row.get(22)?;
+            let preview = match row.get::<_, Option<i64>>(23)? {
+                Some(_) => Some(LinkMetadata {
+                    title: row.get(24)?,
+                    description: row.get(25)?,
+                    site_name: row.get(26)?,
+                    favicon_url: row.get(27)?,
+                    image_url: row.get(28)?,
+                }),
+                None => None,
+            };
 
             let tags: Option<Vec<String>> = if let Some(tags_str) = tags_json {
                 serde_json::from_str(&tags_str).unwrap_or_default()
@@ -59,33 +149,26 @@ pub async fn get_items(state: State<'_, AppState>) -> Result<Vec<ClipItem>, Stri
                 )
             })?;
 
-            let clip = match clip_value["type"].as_str() {
-                Some("text") => Clip::Text {
-                    plain: clip_value["content"].as_str().unwrap_or("").to_string(),
-                },
-                Some("image") => {
-                    let base64_data = clip_value["content"].as_str().unwrap_or("");
-                    let width = clip_value["width"].as_u64().unwrap_or(0) as usize;
-                    let height = clip_value["height"].as_u64().unwrap_or(0) as usize;
-
-                    Clip::Image {
-                        data: base64_data.to_string(),
-                        width,
-                        height,
-                    }
-                }
-                _ => Clip::Text {
-                    plain: "Invalid clip type".to_string(),
-                },
-            };
+            let clip = Clip::from_stored(&clip_value).unwrap_or(Clip::Text {
+                plain: "Invalid clip type".to_string(),
+            });
 
             Ok(ClipItem {
                 id: id.to_string(),
                 clip,
+                title,
                 created_at,
                 category,
                 summary,
                 tags,
+                expires_at,
+                sensitive,
+                ai_status,
+                manual_fields,
+                link,
+                link_health,
+                canonical_url,
+                preview,
             })
         })
         .map_err(|e| format!("Failed to execute query: {e}"))?;
@@ -106,16 +189,46 @@ pub async fn submit_clip(
     summary: String,
     clip_json: String,
     tags: Vec<String>,
+    title: Option<String>,
 ) -> Result<(), String> {
     let db_path = &state.db_path;
 
     let clip: Clip = serde_json::from_str(&clip_json)
         .map_err(|e| format!("Failed to deserialize clip: {}", e))?;
 
-    save_clip(&app_handle, db_path, &clip, &user_category, &summary, &tags)
+    let analysis = ClipAnalysis {
+        category: user_category,
+        tags,
+        title: title.unwrap_or_default(),
+        summary,
+    };
+
+    // everything here was entered by hand, there is nothing left for the AI to do
+    let clip_id = save_clip(&app_handle, db_path, &clip, &analysis, "done")
         .await
         .map_err(|e| format!("Failed to save clip: {}", e))?;
 
+    let manual: Vec<&str> = [
+        ("category", analysis.category.is_empty()),
+        ("tags", analysis.tags.is_empty()),
+        ("title", analysis.title.is_empty()),
+        ("summary", analysis.summary.is_empty()),
+    ]
+    .into_iter()
+    .filter(|(_, empty)| !empty)
+    .map(|(field, _)| field)
+    .collect();
+
+    let conn = Connection::open(db_path).map_err(|e| format!("Failed to open database: {e}"))?;
+    mark_manual_fields(&conn, clip_id, &manual)
+        .map_err(|e| format!("Failed to save manual fields: {}", e))?;
+
+    // filed by hand, so it is a good example for the AI to learn from
+    if !analysis.category.is_empty() {
+        llm::corrections::record_correction(&conn, clip_id)
+            .map_err(|e| format!("Failed to save correction: {}", e))?;
+    }
+
     // Close the popup window
     if let Some(window) = app_handle.get_webview_window("clip-toolbar") {
         window.close().ok();
@@ -133,6 +246,21 @@ pub fn delete_item(
     let conn =
         Connection::open(&state.db_path).map_err(|e| format!("Failed to open database: {e}"))?;
 
+    if let Ok(id) = item_id.parse::<i64>() {
+        llm::cache::forget_clip(&conn, id)
+            .map_err(|error| format!("Failed to clear cached results: {}", error))?;
+        llm::corrections::forget_clip(&conn, id)
+            .map_err(|error| format!("Failed to remove corrections: {}", error))?;
+        llm::embeddings::forget_clip(&conn, id)
+            .map_err(|error| format!("Failed to remove embedding: {}", error))?;
+        fetch::forget_clip(&conn, id)
+            .map_err(|error| format!("Failed to remove page info: {}", error))?;
+        link_health::forget_clip(&conn, id)
+            .map_err(|error| format!("Failed to remove link check: {}", error))?;
+        library::forget_clip(&conn, id)
+            .map_err(|error| format!("Failed to remove answers: {}", error))?;
+    }
+
     let rows_affected = conn
         .execute("DELETE FROM clips WHERE id = ?", params![item_id])
         .map_err(|error| format!("Failed to delete item: {}", error))?;
@@ -141,9 +269,155 @@ pub fn delete_item(
         return Err("Item not found".to_string());
     }
 
+    if let Ok(id) = item_id.parse::<i64>() {
+        if let Err(e) = blobs::remove_clip_blobs(&state.db_path, id) {
+            eprintln!("Failed to remove blobs for clip {}: {}", id, e);
+        }
+    }
+
     app_handle
         .emit("clip-deleted", &item_id)
         .map_err(|e| format!("Failed to emit event: {}", e))?;
 
     Ok(())
 }
+
+/// Set or clear (`None`) how many seconds from now a clip self-destructs
+#[tauri::command]
+pub fn set_item_expiry(
+    state: State<'_, AppState>,
+    item_id: String,
+    expires_in_secs: Option<i64>,
+) -> Result<(), String> {
+    // datetime() turns a negative offset into NULL, which would clear the expiry instead
+    if expires_in_secs.is_some_and(|secs| secs <= 0) {
+        return Err("Expiry must be a positive number of seconds".to_string());
+    }
+
+    let conn =
+        Connection::open(&state.db_path).map_err(|e| format!("Failed to open database: {e}"))?;
+
+    let rows_affected = conn
+        .execute(
+            "UPDATE clips SET expires_at = datetime('now', ?) WHERE id = ?",
+            params![expiry::expiry_modifier(expires_in_secs), item_id],
+        )
+        .map_err(|error| format!("Failed to set expiry: {}", error))?;
+
+    if rows_affected == 0 {
+        return Err("Item not found".to_string());
+    }
+
+    Ok(())
+}
+
+/// Edit a clip by hand. Only the fields passed are changed, and AI reprocessing keeps them
+#[tauri::command]
+pub fn update_item(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    item_id: String,
+    category: Option<String>,
+    tags: Option<Vec<String>>,
+    title: Option<String>,
+    summary: Option<String>,
+) -> Result<(), String> {
+    let id: i64 = item_id
+        .parse()
+        .map_err(|e| format!("Invalid item id: {e}"))?;
+
+    let tags_json = tags
+        .as_ref()
+        .map(serde_json::to_string)
+        .transpose()
+        .map_err(|e| format!("Failed to serialize tags: {e}"))?;
+
+    let mut conn =
+        Connection::open(&state.db_path).map_err(|e| format!("Failed to open database: {e}"))?;
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("Failed to start transaction: {e}"))?;
+
+    let rows_affected = tx
+        .execute(
+            r#"
+            UPDATE clips
+            SET category = COALESCE(?, category),
+                tags = COALESCE(?, tags),
+                title = COALESCE(?, title),
+                summary = COALESCE(?, summary)
+            WHERE id = ?
+            "#,
+            params![category, tags_json, title, summary, id],
+        )
+        .map_err(|e| format!("Failed to update item: {e}"))?;
+
+    if rows_affected == 0 {
+        return Err("Item not found".to_string());
+    }
+
+    let edited: Vec<&str> = [
+        ("category", category.is_some()),
+        ("tags", tags.is_some()),
+        ("title", title.is_some()),
+        ("summary", summary.is_some()),
+    ]
+    .into_iter()
+    .filter(|(_, edited)| *edited)
+    .map(|(field, _)| field)
+    .collect();
+
+    mark_manual_fields(&tx, id, &edited)
+        .map_err(|e| format!("Failed to save manual fields: {e}"))?;
+
+    // an edited category or tags is the AI getting it wrong, learn from it
+    if category.is_some() || tags.is_some() {
+        llm::corrections::record_correction(&tx, id)
+            .map_err(|e| format!("Failed to save correction: {e}"))?;
+    }
+
+    tx.commit()
+        .map_err(|e| format!("Failed to commit transaction: {e}"))?;
+
+    app_handle
+        .emit("clip-updated", &item_id)
+        .map_err(|e| format!("Failed to emit event: {}", e))?;
+
+    Ok(())
+}
+
+/// Instant local guess at a clip's category, tags and title, for pre-filling the save form
+#[tauri::command]
+pub fn suggest_analysis(clip_json: String) -> Result<ClipAnalysis, String> {
+    let clip: Clip = serde_json::from_str(&clip_json)
+        .map_err(|e| format!("Failed to deserialize clip: {}", e))?;
+
+    Ok(ClipAnalysis::fallback(&clip, false))
+}
+
+/// Add fields to a clip's `manual_fields` list
+fn mark_manual_fields(conn: &Connection, clip_id: i64, fields: &[&str]) -> rusqlite::Result<()> {
+    if fields.is_empty() {
+        return Ok(());
+    }
+
+    let current: String = conn.query_row(
+        "SELECT manual_fields FROM clips WHERE id = ?",
+        params![clip_id],
+        |row| row.get(0),
+    )?;
+
+    let mut manual: Vec<String> = serde_json::from_str(&current).unwrap_or_default();
+    for field in fields {
+        if !manual.iter().any(|m| m == field) {
+            manual.push(field.to_string());
+        }
+    }
+
+    conn.execute(
+        "UPDATE clips SET manual_fields = ? WHERE id = ?",
+        params![serde_json::to_string(&manual).unwrap_or_default(), clip_id],
+    )?;
+
+    Ok(())
+}
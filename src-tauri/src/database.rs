@@ -70,7 +70,187 @@ pub fn init_database(app_handle: AppHandle) -> AppResult<std::path::PathBuf> {
             updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
         );"#;
 
-    let statements = vec![links_table, settings_table];
+    // every time an LLM result field had to be replaced with a local default
+    let llm_fallbacks_table = r#"
+        CREATE TABLE if not exists llm_fallbacks (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            task TEXT NOT NULL,
+            field TEXT NOT NULL,
+            reason TEXT NOT NULL,
+            detail TEXT,
+            provider TEXT,
+            model TEXT,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        );"#;
+
+    // background AI work, kept until it succeeds so it survives restarts
+    let ai_jobs_table = r#"
+        CREATE TABLE if not exists ai_jobs (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            clip_id INTEGER NOT NULL,
+            kind TEXT NOT NULL,
+            status TEXT NOT NULL DEFAULT 'queued',
+            attempts INTEGER NOT NULL DEFAULT 0,
+            last_error TEXT,
+            run_after DATETIME DEFAULT CURRENT_TIMESTAMP,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        );"#;
+
+    // one bulk reprocess run, counts let the UI show progress
+    let ai_batches_table = r#"
+        CREATE TABLE if not exists ai_batches (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            total INTEGER NOT NULL,
+            succeeded INTEGER NOT NULL DEFAULT 0,
+            failed INTEGER NOT NULL DEFAULT 0,
+            skipped INTEGER NOT NULL DEFAULT 0,
+            status TEXT NOT NULL DEFAULT 'running',
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            finished_at DATETIME
+        );"#;
+
+    // LLM replies by content, so the same text captured twice only costs one call
+    let llm_cache_table = r#"
+        CREATE TABLE if not exists llm_cache (
+            content_hash TEXT NOT NULL,
+            task TEXT NOT NULL,
+            provider TEXT NOT NULL,
+            model TEXT NOT NULL,
+            prompt_version TEXT NOT NULL,
+            response TEXT NOT NULL,
+            hits INTEGER NOT NULL DEFAULT 0,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            last_used_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            PRIMARY KEY (content_hash, task, provider, model, prompt_version)
+        );"#;
+
+    // every LLM call with its tokens and estimated cost, for usage reports and budgets
+    let llm_usage_table = r#"
+        CREATE TABLE if not exists llm_usage (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            task TEXT NOT NULL,
+            provider TEXT NOT NULL,
+            model TEXT NOT NULL,
+            input_tokens INTEGER NOT NULL DEFAULT 0,
+            output_tokens INTEGER NOT NULL DEFAULT 0,
+            latency_ms INTEGER NOT NULL,
+            cost_usd REAL NOT NULL DEFAULT 0,
+            status TEXT NOT NULL,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        );"#;
+    let llm_usage_index =
+        "CREATE INDEX if not exists llm_usage_created_at ON llm_usage (created_at);";
+
+    // categories and tags the user set by hand, fed back to the LLM as examples
+    let ai_corrections_table = r#"
+        CREATE TABLE if not exists ai_corrections (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            clip_id INTEGER NOT NULL UNIQUE,
+            excerpt TEXT NOT NULL,
+            category TEXT NOT NULL,
+            tags TEXT NOT NULL DEFAULT '[]',
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        );"#;
+
+    // edited prompt templates, every save is a new version. a NULL body resets to the default
+    let prompt_templates_table = r#"
+        CREATE TABLE if not exists prompt_templates (
+            name TEXT NOT NULL,
+            version INTEGER NOT NULL,
+            body TEXT,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            PRIMARY KEY (name, version)
+        );"#;
+
+    // what was found at the URL of a link clip, or why fetching it failed
+    let link_pages_table = r#"
+        CREATE TABLE if not exists link_pages (
+            clip_id INTEGER PRIMARY KEY,
+            url TEXT NOT NULL,
+            final_url TEXT,
+            status_code INTEGER,
+            content_type TEXT,
+            title TEXT,
+            description TEXT,
+            error TEXT,
+            fetched_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        );"#;
+
+    // latest periodic check of each URL clip's link
+    let link_health_table = r#"
+        CREATE TABLE if not exists link_health (
+            clip_id INTEGER PRIMARY KEY,
+            url TEXT NOT NULL,
+            state TEXT NOT NULL,
+            status_code INTEGER,
+            redirect_url TEXT,
+            error TEXT,
+            failures INTEGER NOT NULL DEFAULT 0,
+            checked_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        );"#;
+
+    // link preview card data from a page's OpenGraph and Twitter tags
+    let link_metadata_table = r#"
+        CREATE TABLE if not exists link_metadata (
+            clip_id INTEGER PRIMARY KEY,
+            url TEXT NOT NULL,
+            title TEXT,
+            description TEXT,
+            site_name TEXT,
+            favicon_url TEXT,
+            image_url TEXT,
+            fetched_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        );"#;
+
+    // unit length f32 vectors, little endian. text_hash tells when a clip needs re-embedding
+    let clip_embeddings_table = r#"
+        CREATE TABLE if not exists clip_embeddings (
+            clip_id INTEGER PRIMARY KEY,
+            model TEXT NOT NULL,
+            dims INTEGER NOT NULL,
+            vector BLOB NOT NULL,
+            text_hash TEXT NOT NULL,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        );"#;
+
+    // ask_library conversations, citations are a JSON array of clip ids
+    let library_threads_table = r#"
+        CREATE TABLE if not exists library_threads (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            title TEXT NOT NULL,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        );"#;
+
+    let library_messages_table = r#"
+        CREATE TABLE if not exists library_messages (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            thread_id INTEGER NOT NULL,
+            role TEXT NOT NULL,
+            content TEXT NOT NULL,
+            citations TEXT NOT NULL DEFAULT '[]',
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        );"#;
+
+    let statements = vec![
+        links_table,
+        settings_table,
+        llm_fallbacks_table,
+        ai_jobs_table,
+        ai_batches_table,
+        llm_cache_table,
+        llm_usage_table,
+        llm_usage_index,
+        ai_corrections_table,
+        prompt_templates_table,
+        link_pages_table,
+        link_health_table,
+        link_metadata_table,
+        clip_embeddings_table,
+        library_threads_table,
+        library_messages_table,
+    ];
 
     for (i, stmt) in statements.iter().enumerate() {
         if let Err(e) = conn.execute(stmt, []) {
@@ -80,6 +260,63 @@ pub fn init_database(app_handle: AppHandle) -> AppResult<std::path::PathBuf> {
         }
     }
 
+    // columns added after the first release, existing databases need them added in place
+    let added_columns = vec![
+        ("clips", "expires_at", "DATETIME"),
+        ("clips", "sensitive", "INTEGER NOT NULL DEFAULT 0"),
+        ("clips", "title", "TEXT"),
+        // clips from before the AI queue were analyzed inline, so they count as done
+        ("clips", "ai_status", "TEXT NOT NULL DEFAULT 'done'"),
+        // JSON array of fields the user set by hand, AI results never overwrite these
+        ("clips", "manual_fields", "TEXT NOT NULL DEFAULT '[]'"),
+        ("ai_jobs", "batch_id", "INTEGER"),
+        ("link_pages", "archived_at", "DATETIME"),
+        // the URL of a link clip with tracking parameters and such removed, for deduplication
+        ("clips", "canonical_url", "TEXT"),
+    ];
+
+    for (table, column, definition) in added_columns {
+        if let Err(e) = add_column_if_missing(&conn, table, column, definition) {
+            let error_msg = format!("Failed to add column '{}' to {}: {}", column, table, e);
+            eprintln!("{}", error_msg);
+            return Err(Box::new(Error::new(ErrorKind::Other, error_msg)));
+        }
+    }
+
+    // indexes on added columns can only be created once the columns exist
+    if let Err(e) = conn.execute(
+        "CREATE INDEX if not exists clips_canonical_url ON clips (canonical_url);",
+        [],
+    ) {
+        let error_msg = format!("Failed to create canonical URL index: {}", e);
+        eprintln!("{}", error_msg);
+        return Err(Box::new(Error::new(ErrorKind::Other, error_msg)));
+    }
+
     println!("Database initialized");
     Ok(db_path)
 }
+
+/// Add a column to an existing table unless it is already there
+fn add_column_if_missing(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> rusqlite::Result<()> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let exists = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .filter_map(|name| name.ok())
+        .any(|name| name == column);
+
+    if !exists {
+        conn.execute(
+            &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
+            [],
+        )?;
+        println!("Added column {}.{}", table, column);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,94 @@
+use crate::blobs;
+use crate::fetch;
+use crate::library;
+use crate::link_health;
+use crate::llm;
+use crate::settings::SettingsManager;
+use rusqlite::{params, Connection};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::{thread, time::Duration};
+use tauri::{AppHandle, Emitter};
+
+const SWEEP_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Seconds a clip in this category lives for, if an expiry rule covers it
+pub fn expiry_for_category(settings: &SettingsManager, category: &str) -> Option<i64> {
+    let rules_json = settings.get_setting("expiry_rules")?;
+
+    let rules: HashMap<String, i64> = match serde_json::from_str(&rules_json) {
+        Ok(rules) => rules,
+        Err(e) => {
+            eprintln!("Invalid expiry_rules setting: {}", e);
+            return None;
+        }
+    };
+
+    rules.get(category).copied().filter(|secs| *secs > 0)
+}
+
+/// SQLite datetime modifier for `datetime('now', ?)`, NULL when the clip never expires
+pub fn expiry_modifier(expires_in_secs: Option<i64>) -> Option<String> {
+    expires_in_secs.map(|secs| format!("+{} seconds", secs))
+}
+
+/// Spawn the background thread that removes clips once they expire
+pub fn start_expiry_sweeper(app_handle: AppHandle, db_path: PathBuf) {
+    thread::spawn(move || loop {
+        match sweep_expired_clips(&app_handle, &db_path) {
+            Ok(0) => {}
+            Ok(count) => println!("Removed {} expired clip(s)", count),
+            Err(e) => eprintln!("Expiry sweep failed: {}", e),
+        }
+
+        thread::sleep(SWEEP_INTERVAL);
+    });
+}
+
+pub fn sweep_expired_clips(
+    app_handle: &AppHandle,
+    db_path: &Path,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let conn = Connection::open(db_path)?;
+
+    // make sqlite overwrite deleted rows instead of leaving them in free pages
+    conn.pragma_update(None, "secure_delete", true)?;
+
+    let mut stmt = conn.prepare(
+        "SELECT id FROM clips WHERE expires_at IS NOT NULL AND expires_at <= CURRENT_TIMESTAMP",
+    )?;
+    let expired = stmt
+        .query_map([], |row| row.get::<_, i64>(0))?
+        .collect::<Result<Vec<i64>, _>>()?;
+
+    for id in &expired {
+        purge_clip(app_handle, &conn, db_path, *id)?;
+    }
+
+    Ok(expired.len())
+}
+
+/// Delete a clip row and its blob files, then tell the UI it's gone.
+/// The connection should have `secure_delete` enabled.
+pub fn purge_clip(
+    app_handle: &AppHandle,
+    conn: &Connection,
+    db_path: &Path,
+    id: i64,
+) -> Result<(), Box<dyn std::error::Error>> {
+    llm::cache::forget_clip(conn, id)?;
+    llm::corrections::forget_clip(conn, id)?;
+    llm::embeddings::forget_clip(conn, id)?;
+    fetch::forget_clip(conn, id)?;
+    link_health::forget_clip(conn, id)?;
+    library::forget_clip(conn, id)?;
+    conn.execute("DELETE FROM clips WHERE id = ?", params![id])?;
+
+    if let Err(e) = blobs::remove_clip_blobs(db_path, id) {
+        eprintln!("Failed to remove blobs for clip {}: {}", id, e);
+    }
+
+    app_handle.emit("clip-deleted", id.to_string())?;
+
+    Ok(())
+}
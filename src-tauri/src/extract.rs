@@ -0,0 +1,460 @@
+use scraper::node::Element;
+use scraper::{ElementRef, Html, Node, Selector};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+// never part of the readable text
+const SKIPPED: &[&str] = &[
+    "script", "style", "noscript", "template", "svg", "canvas", "iframe", "form", "button",
+    "select", "nav", "header", "footer", "aside", "dialog",
+];
+
+// elements that start a new line of their own
+const BLOCKS: &[&str] = &[
+    "p",
+    "div",
+    "section",
+    "article",
+    "main",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "li",
+    "ul",
+    "ol",
+    "pre",
+    "blockquote",
+    "table",
+    "tr",
+    "td",
+    "th",
+    "figure",
+    "figcaption",
+    "dl",
+    "dt",
+    "dd",
+    "hr",
+];
+
+// class or id fragments of page chrome that isn't marked up as nav/aside
+const BOILERPLATE: &[&str] = &[
+    "cookie",
+    "banner",
+    "sidebar",
+    "menu",
+    "navbar",
+    "breadcrumb",
+    "share",
+    "social",
+    "comment",
+    "related",
+    "newsletter",
+    "subscribe",
+    "advert",
+];
+
+// images smaller than this on either side are icons, spacers or tracking pixels
+const MIN_IMAGE_SIDE: u32 = 64;
+
+/// The readable parts of an HTML page
+#[derive(Debug, Clone, Default)]
+pub struct PageContent {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    /// main text as light Markdown: `#` headings, `-` list items, one block per line
+    pub text: String,
+    /// the page's preview image (`og:image`), then images in the main text in page order.
+    /// sources are as written in the page, possibly relative
+    pub images: Vec<PageImage>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PageImage {
+    pub src: String,
+    pub alt: Option<String>,
+}
+
+pub fn extract(html: &str) -> PageContent {
+    let document = Html::parse_document(html);
+
+    let title = meta_content(&document, r#"meta[property="og:title"]"#).or_else(|| {
+        select_first(&document, "title")
+            .map(|title| collapse_whitespace(&title.text().collect::<String>()))
+    });
+    let description = meta_content(&document, r#"meta[name="description"]"#)
+        .or_else(|| meta_content(&document, r#"meta[property="og:description"]"#));
+
+    // the article itself if the page marks it up, otherwise the whole body
+    let root = ["article", "main", r#"[role="main"]"#, "body"]
+        .iter()
+        .find_map(|selector| select_first(&document, selector))
+        .unwrap_or_else(|| document.root_element());
+
+    let mut out = String::new();
+    let mut images: Vec<PageImage> = meta_content(&document, r#"meta[property="og:image"]"#)
+        .map(|src| PageImage { src, alt: None })
+        .into_iter()
+        .collect();
+    walk(root, &mut out, &mut images);
+
+    let text = out
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !matches!(*line, "#" | "-"))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    PageContent {
+        title: title.filter(|title| !title.is_empty()),
+        description: description.filter(|description| !description.is_empty()),
+        text,
+        images,
+    }
+}
+
+/// What a link preview card shows, from OpenGraph and Twitter meta tags with plain HTML
+/// fallbacks. Image and favicon URLs are absolute
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LinkMetadata {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub site_name: Option<String>,
+    pub favicon_url: Option<String>,
+    pub image_url: Option<String>,
+}
+
+/// What a page's oEmbed endpoint says about it, only the parts a preview card uses
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct OEmbed {
+    pub title: Option<String>,
+    pub provider_name: Option<String>,
+    pub thumbnail_url: Option<String>,
+}
+
+/// The JSON oEmbed endpoint a page advertises, if any
+pub fn oembed_url(html: &str, base: &Url) -> Option<Url> {
+    let document = Html::parse_document(html);
+    select_first(
+        &document,
+        r#"link[rel="alternate"][type="application/json+oembed"]"#,
+    )
+    .and_then(|link| link.value().attr("href"))
+    .and_then(|href| base.join(href.trim()).ok())
+    .filter(|url| matches!(url.scheme(), "http" | "https"))
+}
+
+/// Preview metadata of a page fetched from `base`. Each field takes the first source present
+/// in a fixed order, OpenGraph, Twitter, the page's oEmbed response and then plain HTML, so
+/// the same page always gives the same result
+pub fn link_metadata(html: &str, base: &Url, oembed: Option<&OEmbed>) -> LinkMetadata {
+    let document = Html::parse_document(html);
+    let first_meta = |selectors: &[&str]| {
+        selectors
+            .iter()
+            .find_map(|selector| meta_content(&document, selector))
+    };
+    let from_oembed = |field: fn(&OEmbed) -> &Option<String>| {
+        oembed
+            .and_then(|oembed| field(oembed).as_deref())
+            .map(collapse_whitespace)
+            .filter(|value| !value.is_empty())
+    };
+    let resolve = |href: String| {
+        base.join(&href)
+            .ok()
+            .filter(|url| matches!(url.scheme(), "http" | "https"))
+            .map(String::from)
+    };
+
+    let title = first_meta(&[
+        r#"meta[property="og:title"]"#,
+        r#"meta[name="twitter:title"]"#,
+    ])
+    .or_else(|| from_oembed(|oembed| &oembed.title))
+    .or_else(|| {
+        select_first(&document, "title")
+            .map(|title| collapse_whitespace(&title.text().collect::<String>()))
+            .filter(|title| !title.is_empty())
+    });
+
+    let description = first_meta(&[
+        r#"meta[property="og:description"]"#,
+        r#"meta[name="twitter:description"]"#,
+        r#"meta[name="description"]"#,
+    ]);
+
+    let site_name = first_meta(&[
+        r#"meta[property="og:site_name"]"#,
+        r#"meta[name="application-name"]"#,
+    ])
+    .or_else(|| from_oembed(|oembed| &oembed.provider_name))
+    .or_else(|| {
+        base.host_str()
+            .map(|host| host.trim_start_matches("www.").to_string())
+    });
+
+    let image_url = first_meta(&[
+        r#"meta[property="og:image:secure_url"]"#,
+        r#"meta[property="og:image"]"#,
+        r#"meta[name="twitter:image"]"#,
+        r#"meta[name="twitter:image:src"]"#,
+    ])
+    .or_else(|| from_oembed(|oembed| &oembed.thumbnail_url))
+    .and_then(resolve);
+
+    // browsers look for /favicon.ico when the page doesn't name an icon
+    let favicon_url = [r#"link[rel~="icon"]"#, r#"link[rel="apple-touch-icon"]"#]
+        .iter()
+        .find_map(|selector| {
+            select_first(&document, selector)
+                .and_then(|link| link.value().attr("href"))
+                .map(|href| href.trim().to_string())
+                .filter(|href| !href.is_empty())
+        })
+        .or_else(|| Some("/favicon.ico".to_string()))
+        .and_then(resolve);
+
+    LinkMetadata {
+        title,
+        description,
+        site_name,
+        favicon_url,
+        image_url,
+    }
+}
+
+pub fn select_first<'a>(document: &'a Html, selector: &str) -> Option<ElementRef<'a>> {
+    let selector = Selector::parse(selector).ok()?;
+    document.select(&selector).next()
+}
+
+/// `content` of the first matching `<meta>` tag, whitespace collapsed
+pub fn meta_content(document: &Html, selector: &str) -> Option<String> {
+    select_first(document, selector)
+        .and_then(|meta| meta.value().attr("content"))
+        .map(collapse_whitespace)
+        .filter(|content| !content.is_empty())
+}
+
+fn walk(element: ElementRef, out: &mut String, images: &mut Vec<PageImage>) {
+    let name = element.value().name();
+    let block = BLOCKS.contains(&name);
+    if block {
+        out.push('\n');
+    }
+    match name {
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+            let level = name[1..].parse::<usize>().unwrap_or(1);
+            out.push_str(&"#".repeat(level));
+            out.push(' ');
+        }
+        "li" => out.push_str("- "),
+        "br" => out.push('\n'),
+        "img" => {
+            if let Some(image) = page_image(element.value()) {
+                if !images.iter().any(|known| known.src == image.src) {
+                    images.push(image);
+                }
+            }
+            return;
+        }
+        // preformatted text keeps its own line breaks
+        "pre" => {
+            out.push_str(&element.text().collect::<String>());
+            out.push('\n');
+            return;
+        }
+        _ => {}
+    }
+
+    for child in element.children() {
+        match child.value() {
+            Node::Text(text) => {
+                // inline markup splits text into nodes, keep the spaces that were between them
+                if text.starts_with(char::is_whitespace) && !out.ends_with([' ', '\n']) {
+                    out.push(' ');
+                }
+                out.push_str(&collapse_whitespace(text));
+                if text.ends_with(char::is_whitespace) && !out.ends_with([' ', '\n']) {
+                    out.push(' ');
+                }
+            }
+            Node::Element(element) => {
+                let skipped = SKIPPED.contains(&element.name()) || is_boilerplate(element);
+                if let Some(child) = ElementRef::wrap(child).filter(|_| !skipped) {
+                    walk(child, out, images);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if block {
+        out.push('\n');
+    }
+}
+
+fn page_image(element: &Element) -> Option<PageImage> {
+    let src = element.attr("src")?.trim();
+    // inline data is mostly placeholders for lazy loading
+    if src.is_empty() || src.starts_with("data:") {
+        return None;
+    }
+
+    let too_small = ["width", "height"].iter().any(|side| {
+        element
+            .attr(side)
+            .and_then(|value| value.trim().trim_end_matches("px").parse::<u32>().ok())
+            .is_some_and(|value| value < MIN_IMAGE_SIDE)
+    });
+    if too_small {
+        return None;
+    }
+
+    Some(PageImage {
+        src: src.to_string(),
+        alt: element
+            .attr("alt")
+            .map(collapse_whitespace)
+            .filter(|alt| !alt.is_empty()),
+    })
+}
+
+fn is_boilerplate(element: &Element) -> bool {
+    if element.attr("hidden").is_some() || element.attr("aria-hidden") == Some("true") {
+        return true;
+    }
+
+    let marker = format!(
+        "{} {}",
+        element.attr("class").unwrap_or_default(),
+        element.id().unwrap_or_default()
+    )
+    .to_lowercase();
+    BOILERPLATE.iter().any(|fragment| marker.contains(fragment))
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base() -> Url {
+        Url::parse("https://www.example.com/articles/post").unwrap()
+    }
+
+    fn page(head: &str) -> String {
+        format!("<html><head>{head}</head><body><p>Body</p></body></html>")
+    }
+
+    #[test]
+    fn prefers_opengraph_then_twitter_then_html() {
+        let all = page(
+            r#"<title>Plain title</title>
+            <meta name="description" content="Plain description">
+            <meta name="twitter:title" content="Twitter title">
+            <meta name="twitter:description" content="Twitter description">
+            <meta property="og:title" content="OG title">
+            <meta property="og:description" content="OG description">"#,
+        );
+        let metadata = link_metadata(&all, &base(), None);
+        assert_eq!(metadata.title.as_deref(), Some("OG title"));
+        assert_eq!(metadata.description.as_deref(), Some("OG description"));
+
+        let twitter = page(
+            r#"<title>Plain title</title>
+            <meta name="description" content="Plain description">
+            <meta name="twitter:title" content="Twitter title">
+            <meta name="twitter:description" content="Twitter description">"#,
+        );
+        let metadata = link_metadata(&twitter, &base(), None);
+        assert_eq!(metadata.title.as_deref(), Some("Twitter title"));
+        assert_eq!(metadata.description.as_deref(), Some("Twitter description"));
+
+        let plain = page(
+            r#"<title>  Plain
+            title </title>
+            <meta name="description" content="Plain description">"#,
+        );
+        let metadata = link_metadata(&plain, &base(), None);
+        assert_eq!(metadata.title.as_deref(), Some("Plain title"));
+        assert_eq!(metadata.description.as_deref(), Some("Plain description"));
+        assert_eq!(metadata.site_name.as_deref(), Some("example.com"));
+    }
+
+    #[test]
+    fn oembed_fills_in_after_meta_tags() {
+        let oembed = OEmbed {
+            title: Some("oEmbed title".to_string()),
+            provider_name: Some("Example Video".to_string()),
+            thumbnail_url: Some("https://cdn.example.com/thumb.jpg".to_string()),
+        };
+
+        let metadata = link_metadata(&page("<title>Plain title</title>"), &base(), Some(&oembed));
+        assert_eq!(metadata.title.as_deref(), Some("oEmbed title"));
+        assert_eq!(metadata.site_name.as_deref(), Some("Example Video"));
+        assert_eq!(
+            metadata.image_url.as_deref(),
+            Some("https://cdn.example.com/thumb.jpg")
+        );
+
+        let tagged = page(r#"<meta property="og:title" content="OG title">"#);
+        let metadata = link_metadata(&tagged, &base(), Some(&oembed));
+        assert_eq!(metadata.title.as_deref(), Some("OG title"));
+    }
+
+    #[test]
+    fn finds_the_oembed_endpoint() {
+        let html = page(
+            r#"<link rel="alternate" type="application/json+oembed" href="/oembed?url=post">"#,
+        );
+        assert_eq!(
+            oembed_url(&html, &base()).map(String::from).as_deref(),
+            Some("https://www.example.com/oembed?url=post")
+        );
+        assert_eq!(oembed_url(&page(""), &base()), None);
+    }
+
+    #[test]
+    fn falls_back_to_the_default_favicon() {
+        let metadata = link_metadata(&page(""), &base(), None);
+        assert_eq!(
+            metadata.favicon_url.as_deref(),
+            Some("https://www.example.com/favicon.ico")
+        );
+
+        let declared = page(r#"<link rel="shortcut icon" href="/static/icon.png">"#);
+        let metadata = link_metadata(&declared, &base(), None);
+        assert_eq!(
+            metadata.favicon_url.as_deref(),
+            Some("https://www.example.com/static/icon.png")
+        );
+    }
+
+    #[test]
+    fn resolves_relative_images_against_the_final_url() {
+        let html = page(r#"<meta property="og:image" content="../images/cover.png">"#);
+        let metadata = link_metadata(&html, &base(), None);
+        assert_eq!(
+            metadata.image_url.as_deref(),
+            Some("https://www.example.com/images/cover.png")
+        );
+    }
+
+    #[test]
+    fn drops_urls_that_are_not_http() {
+        let html = page(
+            r#"<meta property="og:image" content="javascript:alert(1)">
+            <link rel="icon" href="data:image/png;base64,AAAA">"#,
+        );
+        let metadata = link_metadata(&html, &base(), None);
+        assert_eq!(metadata.image_url, None);
+        assert_eq!(metadata.favicon_url, None);
+    }
+}
@@ -0,0 +1,515 @@
+use crate::extract::{self, LinkMetadata, OEmbed, PageContent};
+use crate::settings::SettingsManager;
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use thiserror::Error;
+use url::Url;
+
+pub const USER_AGENT: &str = concat!("Mirror/", env!("CARGO_PKG_VERSION"));
+
+#[derive(Debug, Error)]
+pub enum FetchError {
+    #[error("Request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("Timed out after {0}s")]
+    Timeout(u64),
+    #[error("Server returned HTTP {0}")]
+    Status(u16),
+    #[error("Unsupported content type: {0}")]
+    UnsupportedType(String),
+    #[error("Larger than {0} bytes")]
+    TooLarge(usize),
+    #[error("Invalid oEmbed response: {0}")]
+    InvalidOEmbed(String),
+}
+
+/// Limits for fetching pages behind URL clips, from the `fetch_*` settings
+#[derive(Debug, Clone)]
+pub struct FetchConfig {
+    pub enabled: bool,
+    pub timeout: Duration,
+    /// anything past this is cut off, the start of a page is what matters
+    pub max_bytes: usize,
+    pub max_redirects: usize,
+}
+
+impl FetchConfig {
+    pub fn from_settings(settings: &SettingsManager) -> Self {
+        let number = |key: &str, default: u64| {
+            settings
+                .get_setting(key)
+                .and_then(|value| value.trim().parse::<u64>().ok())
+                .unwrap_or(default)
+        };
+
+        Self {
+            enabled: settings
+                .get_setting("fetch_pages")
+                .is_none_or(|value| value == "true"),
+            timeout: Duration::from_secs(number("fetch_timeout_secs", 15).max(1)),
+            max_bytes: number("fetch_max_bytes", 2_000_000) as usize,
+            max_redirects: number("fetch_max_redirects", 5) as usize,
+        }
+    }
+}
+
+/// A page as it was downloaded
+#[derive(Debug, Clone)]
+pub struct FetchedPage {
+    /// where the redirects ended up
+    pub final_url: String,
+    pub status: u16,
+    pub content_type: String,
+    pub body: String,
+    /// the body was cut off at `max_bytes`
+    pub truncated: bool,
+}
+
+/// An image downloaded for a page archive
+#[derive(Debug, Clone)]
+pub struct FetchedImage {
+    pub content_type: String,
+    pub bytes: Vec<u8>,
+}
+
+impl FetchedPage {
+    /// Title, description and readable text, plain text pages are taken as they are
+    pub fn content(&self) -> PageContent {
+        if self.content_type.starts_with("text/plain") {
+            return PageContent {
+                text: self.body.clone(),
+                ..Default::default()
+            };
+        }
+        extract::extract(&self.body)
+    }
+
+    /// Preview card metadata, plain text pages only get what the URL tells. `oembed` is what
+    /// the page's `oembed_url` returned, if it has one
+    pub fn metadata(&self, oembed: Option<&OEmbed>) -> LinkMetadata {
+        let Ok(base) = Url::parse(&self.final_url) else {
+            return LinkMetadata::default();
+        };
+        if self.content_type.starts_with("text/plain") {
+            return extract::link_metadata("", &base, None);
+        }
+        extract::link_metadata(&self.body, &base, oembed)
+    }
+
+    /// Where the page says more about itself can be read as oEmbed JSON
+    pub fn oembed_url(&self) -> Option<Url> {
+        if self.content_type.starts_with("text/plain") {
+            return None;
+        }
+        extract::oembed_url(&self.body, &Url::parse(&self.final_url).ok()?)
+    }
+}
+
+/// Downloads web pages within the configured limits. Holds no app state, so it works
+/// against any server, a local one included
+pub struct PageFetcher {
+    http: reqwest::Client,
+    config: FetchConfig,
+}
+
+impl PageFetcher {
+    pub fn new(config: FetchConfig) -> Result<Self, FetchError> {
+        let http = reqwest::Client::builder()
+            .user_agent(USER_AGENT)
+            .timeout(config.timeout)
+            .redirect(reqwest::redirect::Policy::limited(config.max_redirects))
+            .build()?;
+
+        Ok(Self { http, config })
+    }
+
+    pub async fn fetch(&self, url: &str) -> Result<FetchedPage, FetchError> {
+        let response = self
+            .get(url, "text/html,application/xhtml+xml,text/plain;q=0.9")
+            .await?;
+
+        let content_type = content_type(&response, "text/html");
+        let readable = ["text/html", "application/xhtml+xml", "text/plain"]
+            .iter()
+            .any(|kind| content_type.starts_with(kind));
+        if !readable {
+            return Err(FetchError::UnsupportedType(content_type));
+        }
+
+        let final_url = response.url().to_string();
+        let status = response.status().as_u16();
+        let (body, truncated) = self.read_body(response).await?;
+
+        Ok(FetchedPage {
+            final_url,
+            status,
+            content_type,
+            body: String::from_utf8_lossy(&body).into_owned(),
+            truncated,
+        })
+    }
+
+    /// Download an image within the same limits. Unlike a page, a cut off image is useless,
+    /// so one over `max_bytes` is an error
+    pub async fn fetch_image(&self, url: &str) -> Result<FetchedImage, FetchError> {
+        let response = self.get(url, "image/*").await?;
+
+        let content_type = content_type(&response, "application/octet-stream");
+        if !content_type.starts_with("image/") {
+            return Err(FetchError::UnsupportedType(content_type));
+        }
+
+        let (bytes, truncated) = self.read_body(response).await?;
+        if truncated {
+            return Err(FetchError::TooLarge(self.config.max_bytes));
+        }
+
+        Ok(FetchedImage {
+            content_type,
+            bytes,
+        })
+    }
+
+    /// Read an oEmbed endpoint within the same limits
+    pub async fn fetch_oembed(&self, url: &str) -> Result<OEmbed, FetchError> {
+        let response = self.get(url, "application/json").await?;
+
+        let (body, truncated) = self.read_body(response).await?;
+        if truncated {
+            return Err(FetchError::TooLarge(self.config.max_bytes));
+        }
+        serde_json::from_slice(&body).map_err(|e| FetchError::InvalidOEmbed(e.to_string()))
+    }
+
+    async fn get(&self, url: &str, accept: &str) -> Result<reqwest::Response, FetchError> {
+        let response = self
+            .http
+            .get(url)
+            .header(reqwest::header::ACCEPT, accept)
+            .send()
+            .await
+            .map_err(|e| self.timed_out(e))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            return Err(FetchError::Status(status.as_u16()));
+        }
+        Ok(response)
+    }
+
+    // read in chunks so an endless or huge body can't fill up memory
+    async fn read_body(
+        &self,
+        mut response: reqwest::Response,
+    ) -> Result<(Vec<u8>, bool), FetchError> {
+        let mut body = Vec::new();
+
+        while let Some(chunk) = response.chunk().await.map_err(|e| self.timed_out(e))? {
+            let room = self.config.max_bytes - body.len();
+            if chunk.len() > room {
+                body.extend_from_slice(&chunk[..room]);
+                return Ok((body, true));
+            }
+            body.extend_from_slice(&chunk);
+        }
+
+        Ok((body, false))
+    }
+
+    fn timed_out(&self, e: reqwest::Error) -> FetchError {
+        if e.is_timeout() {
+            FetchError::Timeout(self.config.timeout.as_secs())
+        } else {
+            FetchError::Http(e)
+        }
+    }
+}
+
+// lowercased, `default` when the server didn't say
+fn content_type(response: &reqwest::Response, default: &str) -> String {
+    response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or(default)
+        .to_lowercase()
+}
+
+/// What is stored about the page behind a URL clip
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinkPage {
+    pub final_url: Option<String>,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub fetched_at: String,
+    /// why the last fetch failed, if it did
+    pub error: Option<String>,
+    /// when an offline copy was saved, see `archive`
+    pub archived_at: Option<String>,
+}
+
+/// Record the outcome of fetching a clip's page, replacing any earlier one. An archived
+/// copy stays listed, it's only replaced by a newer archive
+pub fn save_link_page(
+    conn: &Connection,
+    clip_id: i64,
+    url: &str,
+    result: &Result<(FetchedPage, PageContent), FetchError>,
+) -> rusqlite::Result<()> {
+    match result {
+        Ok((page, content)) => conn.execute(
+            r#"
+            INSERT INTO link_pages
+              (clip_id, url, final_url, status_code, content_type, title, description, error, fetched_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, NULL, CURRENT_TIMESTAMP)
+            ON CONFLICT (clip_id) DO UPDATE SET
+              url = excluded.url, final_url = excluded.final_url,
+              status_code = excluded.status_code, content_type = excluded.content_type,
+              title = excluded.title, description = excluded.description,
+              error = NULL, fetched_at = excluded.fetched_at
+            "#,
+            params![
+                clip_id,
+                url,
+                page.final_url,
+                page.status,
+                page.content_type,
+                content.title,
+                content.description
+            ],
+        )?,
+        Err(e) => conn.execute(
+            r#"
+            INSERT INTO link_pages (clip_id, url, status_code, error, fetched_at)
+            VALUES (?, ?, ?, ?, CURRENT_TIMESTAMP)
+            ON CONFLICT (clip_id) DO UPDATE SET
+              url = excluded.url, final_url = NULL, status_code = excluded.status_code,
+              content_type = NULL, title = NULL, description = NULL,
+              error = excluded.error, fetched_at = excluded.fetched_at
+            "#,
+            params![
+                clip_id,
+                url,
+                match e {
+                    FetchError::Status(status) => Some(*status),
+                    _ => None,
+                },
+                e.to_string()
+            ],
+        )?,
+    };
+    Ok(())
+}
+
+/// Store the preview card metadata of a clip's page, replacing any earlier one
+pub fn save_link_metadata(
+    conn: &Connection,
+    clip_id: i64,
+    url: &str,
+    metadata: &LinkMetadata,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        r#"
+        INSERT OR REPLACE INTO link_metadata
+          (clip_id, url, title, description, site_name, favicon_url, image_url, fetched_at)
+        VALUES (?, ?, ?, ?, ?, ?, ?, CURRENT_TIMESTAMP)
+        "#,
+        params![
+            clip_id,
+            url,
+            metadata.title,
+            metadata.description,
+            metadata.site_name,
+            metadata.favicon_url,
+            metadata.image_url
+        ],
+    )?;
+    Ok(())
+}
+
+/// Drop what was stored about a clip's page. Call before deleting the clip row
+pub fn forget_clip(conn: &Connection, clip_id: i64) -> rusqlite::Result<()> {
+    conn.execute("DELETE FROM link_pages WHERE clip_id = ?", params![clip_id])?;
+    conn.execute(
+        "DELETE FROM link_metadata WHERE clip_id = ?",
+        params![clip_id],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    // a local server answering each request with whatever `respond` returns for its path
+    fn serve(respond: impl Fn(&str) -> String + Send + 'static) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { break };
+                let mut request = [0; 4096];
+                let read = stream.read(&mut request).unwrap_or(0);
+                let request = String::from_utf8_lossy(&request[..read]);
+                let path = request.split_whitespace().nth(1).unwrap_or("/").to_string();
+                let _ = stream.write_all(respond(&path).as_bytes());
+            }
+        });
+        base
+    }
+
+    fn response(status: &str, headers: &[(&str, &str)], body: &str) -> String {
+        let mut response = format!(
+            "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+            status,
+            body.len()
+        );
+        for (name, value) in headers {
+            response.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        format!("{}\r\n{}", response, body)
+    }
+
+    fn html(body: &str) -> String {
+        response(
+            "200 OK",
+            &[("Content-Type", "text/html; charset=utf-8")],
+            body,
+        )
+    }
+
+    fn fetcher(configure: impl FnOnce(&mut FetchConfig)) -> PageFetcher {
+        let mut config = FetchConfig {
+            enabled: true,
+            timeout: Duration::from_secs(5),
+            max_bytes: 1_000,
+            max_redirects: 5,
+        };
+        configure(&mut config);
+        PageFetcher::new(config).unwrap()
+    }
+
+    #[tokio::test]
+    async fn cuts_off_bodies_at_max_bytes() {
+        let base = serve(|_| html(&"a".repeat(100)));
+
+        let page = fetcher(|config| config.max_bytes = 10)
+            .fetch(&base)
+            .await
+            .unwrap();
+        assert_eq!(page.body.len(), 10);
+        assert!(page.truncated);
+
+        let page = fetcher(|config| config.max_bytes = 100)
+            .fetch(&base)
+            .await
+            .unwrap();
+        assert_eq!(page.body.len(), 100);
+        assert!(!page.truncated);
+    }
+
+    #[tokio::test]
+    async fn follows_redirects_up_to_the_limit() {
+        // /hops/3 redirects to /hops/2 and so on down to the page at /hops/0
+        let base = serve(|path| {
+            let hops: usize = path.trim_start_matches("/hops/").parse().unwrap_or(0);
+            if hops == 0 {
+                return html("<p>landed</p>");
+            }
+            let location = format!("/hops/{}", hops - 1);
+            response("302 Found", &[("Location", &location)], "")
+        });
+        let fetcher = fetcher(|config| config.max_redirects = 2);
+
+        let page = fetcher.fetch(&format!("{base}/hops/2")).await.unwrap();
+        assert_eq!(page.final_url, format!("{base}/hops/0"));
+
+        match fetcher.fetch(&format!("{base}/hops/3")).await {
+            Err(FetchError::Http(e)) => assert!(e.is_redirect()),
+            other => panic!("expected a redirect error, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[tokio::test]
+    async fn reports_timeouts() {
+        let base = serve(|_| {
+            thread::sleep(Duration::from_secs(1));
+            html("too late")
+        });
+
+        let result = fetcher(|config| config.timeout = Duration::from_millis(200))
+            .fetch(&base)
+            .await;
+        assert!(matches!(result, Err(FetchError::Timeout(_))));
+    }
+
+    #[tokio::test]
+    async fn rejects_unreadable_content_and_error_statuses() {
+        let base = serve(|path| match path {
+            "/report.pdf" => response("200 OK", &[("Content-Type", "application/pdf")], "%PDF"),
+            _ => response("404 Not Found", &[("Content-Type", "text/html")], "gone"),
+        });
+        let fetcher = fetcher(|_| {});
+
+        match fetcher.fetch(&format!("{base}/report.pdf")).await {
+            Err(FetchError::UnsupportedType(kind)) => assert_eq!(kind, "application/pdf"),
+            other => panic!("expected an unsupported type, got {:?}", other.map(|_| ())),
+        }
+        assert!(matches!(
+            fetcher.fetch(&format!("{base}/missing")).await,
+            Err(FetchError::Status(404))
+        ));
+    }
+
+    #[tokio::test]
+    async fn reads_oembed_responses() {
+        let base = serve(|path| match path {
+            "/oembed" => response(
+                "200 OK",
+                &[("Content-Type", "application/json")],
+                r#"{"type": "video", "title": "A talk", "provider_name": "Tube"}"#,
+            ),
+            _ => response(
+                "200 OK",
+                &[("Content-Type", "application/json")],
+                "not json",
+            ),
+        });
+        let fetcher = fetcher(|_| {});
+
+        let oembed = fetcher
+            .fetch_oembed(&format!("{base}/oembed"))
+            .await
+            .unwrap();
+        assert_eq!(oembed.title.as_deref(), Some("A talk"));
+        assert_eq!(oembed.provider_name.as_deref(), Some("Tube"));
+        assert!(matches!(
+            fetcher.fetch_oembed(&format!("{base}/broken")).await,
+            Err(FetchError::InvalidOEmbed(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn extracts_the_main_text() {
+        let base = serve(|_| {
+            html(
+                r#"<html><head><title>Guide</title></head><body>
+                <nav>Home | About</nav>
+                <article><h1>Setting up</h1><p>Install the tools first.</p></article>
+                <footer>Copyright</footer>
+                </body></html>"#,
+            )
+        });
+
+        let content = fetcher(|_| {}).fetch(&base).await.unwrap().content();
+        assert_eq!(content.title.as_deref(), Some("Guide"));
+        assert!(content.text.contains("Install the tools first."));
+        assert!(!content.text.contains("Home | About"));
+        assert!(!content.text.contains("Copyright"));
+    }
+}
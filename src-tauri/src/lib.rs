@@ -1,11 +1,27 @@
+mod archive;
+mod audit;
+mod blobs;
+mod canonical;
+mod classify;
 mod commands;
 mod database;
+mod expiry;
+mod extract;
+mod fetch;
+mod library;
+mod link_health;
 mod llm;
+mod pii;
+mod queue;
+mod related;
+mod reprocess;
+mod search;
+mod secrets;
 mod settings;
 mod shortcut;
 
-use std::env;
 use std::path::PathBuf;
+use std::sync::Arc;
 use tauri::Manager;
 use tauri_plugin_global_shortcut::GlobalShortcutExt;
 
@@ -30,20 +46,33 @@ pub fn run() {
             app.manage(AppState {
                 db_path: db_path.clone(),
             });
-            settings::init_settings(db_path, app.app_handle().clone())?;
+            app.manage(llm::jobs::InFlightJobsState(Arc::new(
+                llm::jobs::InFlightJobs::default(),
+            )));
+            app.manage(queue::AiQueueState(Arc::new(queue::AiQueue::default())));
+            app.manage(library::PendingQuestions::default());
+            settings::init_settings(db_path.clone(), app.app_handle().clone())?;
 
             let settings_state = app.state::<settings::SettingsManagerState>();
 
-            // openai lib reads openai api key from env var so we need to read from db
-            if let Some(api_key) = settings_state.0.get_setting("llm_api_key") {
-                if !api_key.is_empty() {
-                    env::set_var("OPENAI_API_KEY", api_key);
-                    println!("OpenAI API key loaded from database");
-                } else {
-                    println!("Warning: OpenAI API key is empty in database");
-                }
-            } else {
-                println!("Warning: No OpenAI API key found in database");
+            let llm_config = llm::LlmConfig::from_settings(&settings_state.0);
+            println!(
+                "LLM provider: {} ({})",
+                llm_config.provider.as_str(),
+                llm_config.model
+            );
+            if llm_config.api_key.is_none() {
+                println!("Warning: No LLM API key found in database");
+            }
+
+            // catches up clips from before canonicalization and rules changed while closed
+            match canonical::refresh_canonical_urls(
+                &db_path,
+                &canonical::CanonicalRules::from_settings(&settings_state.0),
+            ) {
+                Ok(0) => {}
+                Ok(changed) => println!("Updated {} canonical URL(s)", changed),
+                Err(e) => eprintln!("Failed to update canonical URLs: {}", e),
             }
 
             let hotkey_str = settings_state.0.get_global_hotkey();
@@ -53,12 +82,47 @@ pub fn run() {
 
             app.global_shortcut().register(shortcut)?;
 
+            expiry::start_expiry_sweeper(app.app_handle().clone(), db_path.clone());
+            link_health::start_link_checker(app.app_handle().clone(), db_path.clone());
+            queue::start_ai_workers(app.app_handle().clone(), db_path)?;
+
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             commands::get_items,
             commands::submit_clip,
             commands::delete_item,
+            commands::set_item_expiry,
+            commands::update_item,
+            commands::suggest_analysis,
+            archive::open_archive,
+            link_health::get_broken_links,
+            link_health::update_moved_links,
+            llm::embeddings::build_embeddings,
+            search::semantic_search,
+            related::get_related_items,
+            library::ask_library,
+            library::cancel_library_question,
+            library::get_library_threads,
+            library::get_library_messages,
+            library::delete_library_thread,
+            audit::scan_secrets,
+            audit::resolve_secret_findings,
+            llm::fallbacks::get_llm_fallback_stats,
+            llm::cache::clear_llm_cache,
+            llm::resilience::get_llm_health,
+            llm::resilience::reset_llm_circuit,
+            llm::usage::get_usage_report,
+            llm::prompts::get_prompt_templates,
+            llm::prompts::get_prompt_template_history,
+            llm::prompts::set_prompt_template,
+            llm::prompts::reset_prompt_template,
+            queue::cancel_ai_job,
+            queue::cancel_all_ai_jobs,
+            queue::get_ai_queue_status,
+            queue::retry_failed_ai_jobs,
+            reprocess::reprocess_items,
+            reprocess::cancel_reprocess,
             settings::get_setting,
             settings::set_setting,
             settings::set_global_hotkey,
@@ -0,0 +1,504 @@
+use crate::llm::chunking::truncate_to_tokens;
+use crate::llm::{LlmClient, LlmError, Role};
+use crate::search::{self, SearchMode};
+use crate::secrets;
+use crate::settings::{SettingsManager, SettingsManagerState};
+use crate::AppState;
+use regex::Regex;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+use tauri::{AppHandle, Emitter, State};
+use tokio_util::sync::CancellationToken;
+
+// clips retrieved as sources for each question
+const SOURCE_COUNT: usize = 8;
+// how much of each clip goes into the prompt
+const SOURCE_TOKENS: usize = 400;
+// earlier messages of a thread sent along with a follow-up question
+const HISTORY_MESSAGES: usize = 6;
+const NOTHING_FOUND: &str = "I couldn't find any clips about that in your library.";
+// what an answer citing a deleted or sensitive clip is replaced with
+const REMOVED_ANSWER: &str =
+    "This answer was removed because a clip it cited was deleted or marked sensitive.";
+const CANCELLED: &str = "Question cancelled";
+
+/// Cancellation handles for questions being answered, by thread id
+#[derive(Default)]
+pub struct PendingQuestions {
+    tokens: Mutex<HashMap<i64, CancellationToken>>,
+}
+
+/// A saved conversation with the library
+#[derive(Debug, Serialize)]
+pub struct LibraryThread {
+    pub id: String,
+    /// the question the thread started with
+    pub title: String,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LibraryMessage {
+    pub id: String,
+    /// user | assistant
+    pub role: String,
+    pub content: String,
+    /// ids of the clips an answer cites, in the order they are first cited
+    pub citations: Vec<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LibraryAnswer {
+    pub thread_id: String,
+    pub message: LibraryMessage,
+}
+
+/// A piece of an answer as it is generated, sent as `library-stream` events
+#[derive(Debug, Clone, Serialize)]
+struct StreamChunk {
+    thread_id: String,
+    text: String,
+}
+
+/// Sent as `library-question-started` once a question has a thread, so it can be cancelled
+#[derive(Debug, Clone, Serialize)]
+struct QuestionStarted {
+    thread_id: String,
+}
+
+fn citation_regex() -> &'static Regex {
+    static CITATION: OnceLock<Regex> = OnceLock::new();
+    CITATION.get_or_init(|| Regex::new(r"\[clip:\s*(\d+)\]").expect("invalid regex"))
+}
+
+/// Clip ids cited in `answer` that were among the sources, first citation first
+fn citations(answer: &str, sources: &[i64]) -> Vec<String> {
+    let mut cited: Vec<String> = Vec::new();
+    for caps in citation_regex().captures_iter(answer) {
+        let Ok(id) = caps[1].parse::<i64>() else {
+            continue;
+        };
+        if sources.contains(&id) && !cited.contains(&id.to_string()) {
+            cited.push(id.to_string());
+        }
+    }
+    cited
+}
+
+// the clips most relevant to the question, keywords alone if semantic search is unavailable.
+// secrets never go to the model
+async fn retrieve_sources(
+    db_path: &Path,
+    settings: &SettingsManager,
+    query: &str,
+) -> Result<Vec<(i64, String)>, String> {
+    let hits =
+        match search::search(db_path, settings, query, SOURCE_COUNT, SearchMode::Hybrid).await {
+            Ok(hits) => hits,
+            Err(e) => {
+                eprintln!("Semantic search failed, using keywords only: {}", e);
+                search::search(db_path, settings, query, SOURCE_COUNT, SearchMode::Keyword)
+                    .await
+                    .map_err(|e| format!("Failed to search clips: {e}"))?
+            }
+        };
+
+    let conn = Connection::open(db_path).map_err(|e| format!("Failed to open database: {e}"))?;
+    let mut stmt = conn
+        .prepare(
+            r#"
+            SELECT
+              clips.title,
+              clips.category,
+              clips.summary,
+              clips.created_at,
+              link_pages.title,
+              CASE WHEN json_extract(clips.clip, '$.type') = 'text'
+                THEN json_extract(clips.clip, '$.content') END
+            FROM clips
+            LEFT JOIN link_pages ON link_pages.clip_id = clips.id
+            WHERE clips.id = ? AND clips.sensitive = 0 AND clips.ai_status != 'skipped'
+            "#,
+        )
+        .map_err(|e| format!("Failed to prepare statement: {e}"))?;
+
+    let mut sources = Vec::new();
+    for hit in hits {
+        let Ok(id) = hit.id.parse::<i64>() else {
+            continue;
+        };
+        let source = stmt
+            .query_row(params![id], |row| {
+                let title: Option<String> = row.get(0)?;
+                let category: Option<String> = row.get(1)?;
+                let summary: Option<String> = row.get(2)?;
+                let created_at: String = row.get(3)?;
+                let page_title: Option<String> = row.get(4)?;
+                let content: Option<String> = row.get(5)?;
+
+                let mut text = format!(
+                    "[clip:{}] {} ({}, saved {})",
+                    id,
+                    title
+                        .or(page_title)
+                        .unwrap_or_else(|| "Untitled".to_string()),
+                    category.unwrap_or_else(|| "uncategorized".to_string()),
+                    created_at
+                );
+                if let Some(summary) = summary.filter(|summary| !summary.trim().is_empty()) {
+                    text.push_str(&format!("\nSummary: {}", summary.trim()));
+                }
+                if let Some(content) = content.filter(|content| !content.trim().is_empty()) {
+                    text.push_str(&format!(
+                        "\nContent:\n{}",
+                        truncate_to_tokens(content.trim(), SOURCE_TOKENS)
+                    ));
+                }
+                Ok(text)
+            })
+            .optional()
+            .map_err(|e| format!("Failed to load clip {id}: {e}"))?;
+
+        // hand-submitted clips are never scanned on the way in
+        if let Some(source) = source.filter(|source| secrets::detect_secrets(source).is_empty()) {
+            sources.push((id, source));
+        }
+    }
+
+    Ok(sources)
+}
+
+// the end of a thread, starting with a question so the turns alternate properly
+fn thread_history(conn: &Connection, thread_id: i64) -> rusqlite::Result<Vec<(Role, String)>> {
+    let mut stmt = conn.prepare(
+        "SELECT role, content FROM library_messages WHERE thread_id = ? ORDER BY id DESC LIMIT ?",
+    )?;
+    let mut messages = stmt
+        .query_map(params![thread_id, HISTORY_MESSAGES], |row| {
+            let role = match row.get::<_, String>(0)?.as_str() {
+                "assistant" => Role::Assistant,
+                _ => Role::User,
+            };
+            Ok((role, row.get(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    messages.reverse();
+
+    while messages
+        .first()
+        .is_some_and(|(role, _)| *role == Role::Assistant)
+    {
+        messages.remove(0);
+    }
+    Ok(messages)
+}
+
+/// Answer a question about the saved clips, citing them by id. The answer streams in as
+/// `library-stream` events and is saved to the thread, a new one unless `thread_id` is given.
+/// A thread takes one question at a time
+#[tauri::command]
+pub async fn ask_library(
+    app_handle: AppHandle,
+    state: State<'_, AppState>,
+    settings: State<'_, SettingsManagerState>,
+    pending: State<'_, PendingQuestions>,
+    question: String,
+    thread_id: Option<String>,
+) -> Result<LibraryAnswer, String> {
+    let question = question.trim().to_string();
+    if question.is_empty() {
+        return Err("Question can't be empty".to_string());
+    }
+
+    let conn =
+        Connection::open(&state.db_path).map_err(|e| format!("Failed to open database: {e}"))?;
+
+    let (thread_id, history, new_thread) = match thread_id {
+        Some(thread_id) => {
+            let thread_id = thread_id
+                .parse::<i64>()
+                .map_err(|_| "Invalid thread id".to_string())?;
+            let exists = conn
+                .query_row(
+                    "SELECT 1 FROM library_threads WHERE id = ?",
+                    params![thread_id],
+                    |_| Ok(()),
+                )
+                .optional()
+                .map_err(|e| format!("Failed to load thread: {e}"))?
+                .is_some();
+            if !exists {
+                return Err("Thread not found".to_string());
+            }
+            let history = thread_history(&conn, thread_id)
+                .map_err(|e| format!("Failed to load thread: {e}"))?;
+            (thread_id, history, false)
+        }
+        None => {
+            let title: String = question.chars().take(80).collect();
+            let thread_id: i64 = conn
+                .query_row(
+                    "INSERT INTO library_threads (title) VALUES (?) RETURNING id",
+                    params![title],
+                    |row| row.get(0),
+                )
+                .map_err(|e| format!("Failed to create thread: {e}"))?;
+            (thread_id, Vec::new(), true)
+        }
+    };
+
+    let token = CancellationToken::new();
+    {
+        // one question at a time per thread, the next one follows up on its answer
+        let mut tokens = pending.tokens.lock().unwrap();
+        if tokens.contains_key(&thread_id) {
+            return Err("A question is already being answered in this thread".to_string());
+        }
+        tokens.insert(thread_id, token.clone());
+    }
+    let _ = app_handle.emit(
+        "library-question-started",
+        QuestionStarted {
+            thread_id: thread_id.to_string(),
+        },
+    );
+
+    let answer = answer(
+        &app_handle,
+        &state.db_path,
+        &settings.0,
+        thread_id,
+        &history,
+        &question,
+        &token,
+    )
+    .await;
+    pending.tokens.lock().unwrap().remove(&thread_id);
+    let (answer, cited) = match answer {
+        Ok(answer) => answer,
+        Err(e) => {
+            // a thread that never got an answer isn't worth keeping
+            if new_thread {
+                let _ = conn.execute(
+                    "DELETE FROM library_threads WHERE id = ?",
+                    params![thread_id],
+                );
+            }
+            return Err(e);
+        }
+    };
+
+    conn.execute(
+        "INSERT INTO library_messages (thread_id, role, content) VALUES (?, 'user', ?)",
+        params![thread_id, question],
+    )
+    .map_err(|e| format!("Failed to save question: {e}"))?;
+    let message = conn
+        .query_row(
+            r#"
+            INSERT INTO library_messages (thread_id, role, content, citations)
+            VALUES (?, 'assistant', ?, ?)
+            RETURNING id, created_at
+            "#,
+            params![
+                thread_id,
+                answer,
+                serde_json::to_string(&cited).unwrap_or_else(|_| "[]".to_string())
+            ],
+            |row| {
+                Ok(LibraryMessage {
+                    id: row.get::<_, i64>(0)?.to_string(),
+                    role: "assistant".to_string(),
+                    content: answer.clone(),
+                    citations: cited.clone(),
+                    created_at: row.get(1)?,
+                })
+            },
+        )
+        .map_err(|e| format!("Failed to save answer: {e}"))?;
+    conn.execute(
+        "UPDATE library_threads SET updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+        params![thread_id],
+    )
+    .map_err(|e| format!("Failed to update thread: {e}"))?;
+
+    Ok(LibraryAnswer {
+        thread_id: thread_id.to_string(),
+        message,
+    })
+}
+
+// the answer and the clips it cites
+async fn answer(
+    app_handle: &AppHandle,
+    db_path: &Path,
+    settings: &SettingsManager,
+    thread_id: i64,
+    history: &[(Role, String)],
+    question: &str,
+    token: &CancellationToken,
+) -> Result<(String, Vec<String>), String> {
+    let emit = |text: &str| {
+        let _ = app_handle.emit(
+            "library-stream",
+            StreamChunk {
+                thread_id: thread_id.to_string(),
+                text: text.to_string(),
+            },
+        );
+    };
+
+    // a follow-up like "and on windows?" only makes sense with the question before it
+    let previous = history
+        .iter()
+        .rev()
+        .find(|(role, _)| *role == Role::User)
+        .map(|(_, text)| text.as_str());
+    let query = match previous {
+        Some(previous) => format!("{} {}", previous, question),
+        None => question.to_string(),
+    };
+
+    let sources = tokio::select! {
+        _ = token.cancelled() => return Err(CANCELLED.to_string()),
+        sources = retrieve_sources(db_path, settings, &query) => sources?,
+    };
+    if sources.is_empty() {
+        emit(NOTHING_FOUND);
+        return Ok((NOTHING_FOUND.to_string(), Vec::new()));
+    }
+
+    let source_ids: Vec<i64> = sources.iter().map(|(id, _)| *id).collect();
+    let sources = sources
+        .into_iter()
+        .map(|(_, text)| text)
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    let llm = LlmClient::from_settings(settings, db_path).with_cancel(token.clone());
+    let answer = llm
+        .answer_question(history, &sources, question, &emit)
+        .await
+        .map_err(|e| match e {
+            LlmError::Cancelled => CANCELLED.to_string(),
+            e => format!("Failed to answer question: {e}"),
+        })?;
+
+    let cited = citations(&answer, &source_ids);
+    Ok((answer, cited))
+}
+
+/// Stop answering the question being asked in a thread. Returns whether one was running
+#[tauri::command]
+pub fn cancel_library_question(
+    pending: State<'_, PendingQuestions>,
+    thread_id: String,
+) -> Result<bool, String> {
+    let thread_id = thread_id
+        .parse::<i64>()
+        .map_err(|_| "Invalid thread id".to_string())?;
+
+    match pending.tokens.lock().unwrap().get(&thread_id) {
+        Some(token) => {
+            token.cancel();
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+/// Replace answers citing a clip, they quote it. Call before deleting the clip row
+pub fn forget_clip(conn: &Connection, clip_id: i64) -> rusqlite::Result<()> {
+    conn.execute(
+        r#"
+        UPDATE library_messages SET content = ?, citations = '[]'
+        WHERE EXISTS (SELECT 1 FROM json_each(library_messages.citations) WHERE value = ?)
+        "#,
+        params![REMOVED_ANSWER, clip_id.to_string()],
+    )?;
+    Ok(())
+}
+
+/// Saved conversations, most recently active first
+#[tauri::command]
+pub fn get_library_threads(state: State<'_, AppState>) -> Result<Vec<LibraryThread>, String> {
+    let conn =
+        Connection::open(&state.db_path).map_err(|e| format!("Failed to open database: {e}"))?;
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, title, created_at, updated_at FROM library_threads ORDER BY updated_at DESC, id DESC",
+        )
+        .map_err(|e| format!("Failed to prepare statement: {e}"))?;
+
+    let threads = stmt
+        .query_map([], |row| {
+            Ok(LibraryThread {
+                id: row.get::<_, i64>(0)?.to_string(),
+                title: row.get(1)?,
+                created_at: row.get(2)?,
+                updated_at: row.get(3)?,
+            })
+        })
+        .map_err(|e| format!("Failed to query threads: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to read threads: {e}"))?;
+
+    Ok(threads)
+}
+
+/// Every question and answer in a thread, oldest first
+#[tauri::command]
+pub fn get_library_messages(
+    state: State<'_, AppState>,
+    thread_id: String,
+) -> Result<Vec<LibraryMessage>, String> {
+    let conn =
+        Connection::open(&state.db_path).map_err(|e| format!("Failed to open database: {e}"))?;
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, role, content, citations, created_at FROM library_messages WHERE thread_id = ? ORDER BY id",
+        )
+        .map_err(|e| format!("Failed to prepare statement: {e}"))?;
+
+    let messages = stmt
+        .query_map(params![thread_id], |row| {
+            Ok(LibraryMessage {
+                id: row.get::<_, i64>(0)?.to_string(),
+                role: row.get(1)?,
+                content: row.get(2)?,
+                citations: serde_json::from_str(&row.get::<_, String>(3)?).unwrap_or_default(),
+                created_at: row.get(4)?,
+            })
+        })
+        .map_err(|e| format!("Failed to query messages: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to read messages: {e}"))?;
+
+    Ok(messages)
+}
+
+#[tauri::command]
+pub fn delete_library_thread(state: State<'_, AppState>, thread_id: String) -> Result<(), String> {
+    let conn =
+        Connection::open(&state.db_path).map_err(|e| format!("Failed to open database: {e}"))?;
+
+    conn.execute(
+        "DELETE FROM library_messages WHERE thread_id = ?",
+        params![thread_id],
+    )
+    .map_err(|e| format!("Failed to delete messages: {e}"))?;
+    conn.execute(
+        "DELETE FROM library_threads WHERE id = ?",
+        params![thread_id],
+    )
+    .map_err(|e| format!("Failed to delete thread: {e}"))?;
+
+    Ok(())
+}
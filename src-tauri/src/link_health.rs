@@ -0,0 +1,361 @@
+use crate::canonical::CanonicalRules;
+use crate::fetch::{self, FetchConfig, USER_AGENT};
+use crate::llm::embeddings;
+use crate::queue;
+use crate::settings::SettingsManagerState;
+use crate::shortcut::is_url;
+use crate::AppState;
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
+use url::Url;
+
+// how often the checker looks for links that are due
+const POLL_INTERVAL: Duration = Duration::from_secs(10 * 60);
+// links checked per pass, the rest wait for the next one
+const BATCH_SIZE: usize = 50;
+// failed checks in a row before an unreachable link counts as dead
+const MAX_FAILURES: i64 = 3;
+
+/// The last check of a URL clip's link
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinkHealth {
+    /// ok | moved | broken | unreachable
+    pub state: String,
+    pub status_code: Option<u16>,
+    /// where a permanent redirect points, for moved links
+    pub redirect_url: Option<String>,
+    pub error: Option<String>,
+    pub checked_at: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BrokenLink {
+    pub id: String,
+    pub url: String,
+    #[serde(flatten)]
+    pub health: LinkHealth,
+}
+
+struct CheckOutcome {
+    state: &'static str,
+    status_code: Option<u16>,
+    redirect_url: Option<String>,
+    error: Option<String>,
+}
+
+impl CheckOutcome {
+    fn unreachable(status_code: Option<u16>, error: String) -> Self {
+        Self {
+            state: "unreachable",
+            status_code,
+            redirect_url: None,
+            error: Some(error),
+        }
+    }
+}
+
+/// Spawn the background task that re-checks stored links every `link_check_interval_hours`
+pub fn start_link_checker(app_handle: AppHandle, db_path: PathBuf) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            match check_due_links(&app_handle, &db_path).await {
+                Ok(0) => {}
+                Ok(count) => println!("Checked {} link(s)", count),
+                Err(e) => eprintln!("Link check failed: {}", e),
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    });
+}
+
+async fn check_due_links(
+    app_handle: &AppHandle,
+    db_path: &Path,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let settings = &app_handle.state::<SettingsManagerState>().0;
+    let interval_hours = settings
+        .get_setting("link_check_interval_hours")
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .unwrap_or(168);
+    if interval_hours == 0 {
+        return Ok(0);
+    }
+    let config = FetchConfig::from_settings(settings);
+
+    let due = {
+        let conn = Connection::open(db_path)?;
+        // the LIKE only narrows things down, is_url decides
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT clips.id, json_extract(clips.clip, '$.content'), link_health.failures, link_health.state
+            FROM clips
+            LEFT JOIN link_health ON link_health.clip_id = clips.id
+            WHERE json_extract(clips.clip, '$.type') = 'text'
+              AND json_extract(clips.clip, '$.content') LIKE 'http%'
+              -- links in sensitive clips can carry credentials or tokens, they stay put
+              AND clips.sensitive = 0 AND clips.ai_status != 'skipped'
+              AND (link_health.checked_at IS NULL OR link_health.checked_at <= datetime('now', ?))
+            ORDER BY link_health.checked_at IS NOT NULL, link_health.checked_at
+            "#,
+        )?;
+        let rows = stmt
+            .query_map(params![format!("-{} hours", interval_hours)], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, Option<i64>>(2)?.unwrap_or(0),
+                    row.get::<_, Option<String>>(3)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        rows.into_iter()
+            .filter(|(_, url, _, _)| is_url(url))
+            .take(BATCH_SIZE)
+            .collect::<Vec<_>>()
+    };
+    if due.is_empty() {
+        return Ok(0);
+    }
+
+    let client = reqwest::Client::builder()
+        .user_agent(USER_AGENT)
+        .timeout(config.timeout)
+        // redirects are followed by hand to tell permanent ones apart
+        .redirect(reqwest::redirect::Policy::none())
+        .build()?;
+
+    for (clip_id, url, failures, previous_state) in &due {
+        let mut outcome = check_link(&client, url, config.max_redirects).await;
+
+        let failures = if outcome.state == "unreachable" {
+            failures + 1
+        } else {
+            0
+        };
+        if failures >= MAX_FAILURES {
+            outcome.state = "broken";
+        }
+
+        Connection::open(db_path)?.execute(
+            r#"
+            INSERT INTO link_health
+              (clip_id, url, state, status_code, redirect_url, error, failures, checked_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, CURRENT_TIMESTAMP)
+            ON CONFLICT (clip_id) DO UPDATE SET
+              url = excluded.url, state = excluded.state, status_code = excluded.status_code,
+              redirect_url = excluded.redirect_url, error = excluded.error,
+              failures = excluded.failures, checked_at = excluded.checked_at
+            "#,
+            params![
+                clip_id,
+                url,
+                outcome.state,
+                outcome.status_code,
+                outcome.redirect_url,
+                outcome.error,
+                failures
+            ],
+        )?;
+
+        if previous_state.as_deref() != Some(outcome.state) {
+            let _ = app_handle.emit("clip-updated", clip_id.to_string());
+        }
+    }
+
+    Ok(due.len())
+}
+
+/// HEAD a link, falling back to GET for servers that don't answer HEAD properly, and follow
+/// up to `max_redirects` redirects. Only a chain of permanent redirects makes a link moved
+async fn check_link(client: &reqwest::Client, url: &str, max_redirects: usize) -> CheckOutcome {
+    let Ok(original) = Url::parse(url.trim()) else {
+        return CheckOutcome::unreachable(None, "Not a valid URL".to_string());
+    };
+    let mut current = original.clone();
+    let mut permanent = true;
+
+    for _ in 0..=max_redirects {
+        let response = match client.head(current.clone()).send().await {
+            Ok(response) if response.status().as_u16() < 400 => Ok(response),
+            // the body is never read, a GET costs little more than the HEAD
+            _ => client.get(current.clone()).send().await,
+        };
+        let response = match response {
+            Ok(response) => response,
+            Err(e) => return CheckOutcome::unreachable(None, e.to_string()),
+        };
+
+        let status = response.status();
+        if status.is_redirection() {
+            let next = response
+                .headers()
+                .get(reqwest::header::LOCATION)
+                .and_then(|location| location.to_str().ok())
+                .and_then(|location| current.join(location).ok());
+            let Some(next) = next else {
+                return CheckOutcome::unreachable(
+                    Some(status.as_u16()),
+                    "Redirect without a valid location".to_string(),
+                );
+            };
+
+            permanent &= matches!(status.as_u16(), 301 | 308);
+            current = next;
+            continue;
+        }
+
+        let status_code = Some(status.as_u16());
+        return match status.as_u16() {
+            200..=299 if permanent && current != original => CheckOutcome {
+                state: "moved",
+                status_code,
+                redirect_url: Some(current.to_string()),
+                error: None,
+            },
+            200..=299 => CheckOutcome {
+                state: "ok",
+                status_code,
+                redirect_url: None,
+                error: None,
+            },
+            // gone for good, no point waiting for more failures
+            404 | 410 => CheckOutcome {
+                state: "broken",
+                status_code,
+                redirect_url: None,
+                error: Some(format!("HTTP {}", status.as_u16())),
+            },
+            // blocked, rate limited or down, it may well work next time
+            _ => CheckOutcome::unreachable(status_code, format!("HTTP {}", status.as_u16())),
+        };
+    }
+
+    CheckOutcome::unreachable(None, format!("More than {} redirects", max_redirects))
+}
+
+/// Drop a clip's link check. Call before deleting the clip row
+pub fn forget_clip(conn: &Connection, clip_id: i64) -> rusqlite::Result<()> {
+    conn.execute(
+        "DELETE FROM link_health WHERE clip_id = ?",
+        params![clip_id],
+    )?;
+    Ok(())
+}
+
+/// URL clips whose link is broken or has moved, moved ones can be fixed with `update_moved_links`
+#[tauri::command]
+pub fn get_broken_links(state: State<'_, AppState>) -> Result<Vec<BrokenLink>, String> {
+    let conn =
+        Connection::open(&state.db_path).map_err(|e| format!("Failed to open database: {e}"))?;
+
+    let mut stmt = conn
+        .prepare(
+            r#"
+            SELECT clip_id, url, state, status_code, redirect_url, error, checked_at
+            FROM link_health
+            WHERE state IN ('broken', 'moved')
+            ORDER BY state, checked_at DESC
+            "#,
+        )
+        .map_err(|e| format!("Failed to prepare statement: {e}"))?;
+
+    let links = stmt
+        .query_map([], |row| {
+            Ok(BrokenLink {
+                id: row.get::<_, i64>(0)?.to_string(),
+                url: row.get(1)?,
+                health: LinkHealth {
+                    state: row.get(2)?,
+                    status_code: row.get(3)?,
+                    redirect_url: row.get(4)?,
+                    error: row.get(5)?,
+                    checked_at: row.get(6)?,
+                },
+            })
+        })
+        .map_err(|e| format!("Failed to query broken links: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to read broken links: {e}"))?;
+
+    Ok(links)
+}
+
+/// Replace the URL of moved link clips with where they redirect to. Updates the given
+/// items, or every moved link when none are given. What was stored about the old page is
+/// dropped and the clips are analyzed again. Returns how many were updated
+#[tauri::command]
+pub fn update_moved_links(
+    app_handle: AppHandle,
+    state: State<'_, AppState>,
+    settings: State<'_, SettingsManagerState>,
+    item_ids: Option<Vec<String>>,
+) -> Result<usize, String> {
+    let rules = CanonicalRules::from_settings(&settings.0);
+    let mut conn =
+        Connection::open(&state.db_path).map_err(|e| format!("Failed to open database: {e}"))?;
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("Failed to start transaction: {e}"))?;
+
+    let moved: Vec<(i64, String)> = {
+        let mut stmt = tx
+            .prepare(
+                r#"
+                SELECT link_health.clip_id, link_health.redirect_url
+                FROM link_health JOIN clips ON clips.id = link_health.clip_id
+                WHERE link_health.state = 'moved' AND link_health.redirect_url IS NOT NULL
+                  AND clips.sensitive = 0 AND clips.ai_status != 'skipped'
+                "#,
+            )
+            .map_err(|e| format!("Failed to prepare statement: {e}"))?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(|e| format!("Failed to query moved links: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to read moved links: {e}"))?;
+        rows
+    };
+    let wanted = |id: i64| {
+        item_ids
+            .as_ref()
+            .is_none_or(|ids| ids.iter().any(|item_id| item_id == &id.to_string()))
+    };
+    let moved: Vec<(i64, String)> = moved.into_iter().filter(|(id, _)| wanted(*id)).collect();
+
+    for (clip_id, redirect_url) in &moved {
+        tx.execute(
+            r#"
+            UPDATE clips SET clip = json_set(clip, '$.content', ?), canonical_url = ?, ai_status = 'pending'
+            WHERE id = ?
+            "#,
+            params![redirect_url, rules.canonicalize(redirect_url), clip_id],
+        )
+        .map_err(|e| format!("Failed to update link: {e}"))?;
+        // the new URL answered fine on the last check
+        tx.execute(
+            "UPDATE link_health SET url = redirect_url, redirect_url = NULL, state = 'ok' WHERE clip_id = ?",
+            params![clip_id],
+        )
+        .map_err(|e| format!("Failed to update link check: {e}"))?;
+        // the page, preview and embedding all describe the old URL
+        fetch::forget_clip(&tx, *clip_id)
+            .and_then(|_| embeddings::forget_clip(&tx, *clip_id))
+            .map_err(|e| format!("Failed to clear old page info: {e}"))?;
+    }
+
+    tx.commit()
+        .map_err(|e| format!("Failed to commit link updates: {e}"))?;
+
+    for (clip_id, _) in &moved {
+        if let Err(e) = queue::enqueue_analysis(&app_handle, &state.db_path, *clip_id) {
+            eprintln!("Failed to queue analysis of moved clip {}: {}", clip_id, e);
+        }
+        let _ = app_handle.emit("clip-updated", clip_id.to_string());
+    }
+    println!("Updated {} moved link(s)", moved.len());
+
+    Ok(moved.len())
+}
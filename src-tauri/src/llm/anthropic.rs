@@ -0,0 +1,204 @@
+use super::provider::{
+    for_each_line, LlmError, LlmProvider, LlmRequest, LlmResponse, Part, Role, TokenUsage,
+};
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+const DEFAULT_BASE_URL: &str = "https://api.anthropic.com";
+const API_VERSION: &str = "2023-06-01";
+
+/// Anthropic's Messages API
+pub struct AnthropicProvider {
+    http: reqwest::Client,
+    model: String,
+    api_key: Option<String>,
+    base_url: String,
+}
+
+impl AnthropicProvider {
+    pub fn new(model: String, api_key: Option<String>, base_url: Option<String>) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            model,
+            api_key,
+            base_url: base_url
+                .unwrap_or_else(|| DEFAULT_BASE_URL.to_string())
+                .trim_end_matches('/')
+                .to_string(),
+        }
+    }
+
+    fn body(&self, request: &LlmRequest, stream: bool) -> Value {
+        let messages: Vec<Value> = request
+            .messages
+            .iter()
+            .map(|message| {
+                let content: Vec<Value> = message
+                    .parts
+                    .iter()
+                    .map(|part| match part {
+                        Part::Text(text) => json!({ "type": "text", "text": text }),
+                        Part::PngImage(data) => json!({
+                            "type": "image",
+                            "source": { "type": "base64", "media_type": "image/png", "data": data }
+                        }),
+                    })
+                    .collect();
+
+                json!({
+                    "role": match message.role {
+                        Role::User => "user",
+                        Role::Assistant => "assistant",
+                    },
+                    "content": content,
+                })
+            })
+            .collect();
+
+        // the Messages API has no response schema option, so spell it out in the prompt
+        let system = match &request.response_schema {
+            Some(schema) => format!(
+                "{}\n\nYour reply must be a single JSON object matching this JSON schema:\n{}",
+                request.system, schema.schema
+            ),
+            None => request.system.clone(),
+        };
+
+        json!({
+            "model": self.model,
+            "max_tokens": request.max_output_tokens,
+            "system": system,
+            "messages": messages,
+            "stream": stream,
+        })
+    }
+
+    async fn send(
+        &self,
+        request: &LlmRequest,
+        stream: bool,
+    ) -> Result<reqwest::Response, LlmError> {
+        let api_key = self
+            .api_key
+            .as_deref()
+            .ok_or(LlmError::MissingApiKey(self.name()))?;
+
+        let response = self
+            .http
+            .post(format!("{}/v1/messages", self.base_url))
+            .header("x-api-key", api_key)
+            .header("anthropic-version", API_VERSION)
+            .json(&self.body(request, stream))
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(LlmError::from_response(self.name(), response).await);
+        }
+        Ok(response)
+    }
+}
+
+#[derive(Deserialize)]
+struct MessagesResponse {
+    content: Vec<ContentBlock>,
+    usage: Option<Usage>,
+}
+
+#[derive(Deserialize)]
+struct Usage {
+    input_tokens: u32,
+    output_tokens: u32,
+}
+
+#[derive(Deserialize)]
+struct ContentBlock {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    text: String,
+}
+
+#[async_trait]
+impl LlmProvider for AnthropicProvider {
+    fn name(&self) -> &'static str {
+        "anthropic"
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    async fn complete(&self, request: &LlmRequest) -> Result<LlmResponse, LlmError> {
+        let response = self.send(request, false).await?;
+
+        let body: MessagesResponse = response
+            .json()
+            .await
+            .map_err(|e| LlmError::InvalidResponse(self.name(), e.to_string()))?;
+
+        let text = body
+            .content
+            .iter()
+            .filter(|block| block.kind == "text")
+            .map(|block| block.text.as_str())
+            .collect::<Vec<_>>()
+            .join("");
+
+        Ok(LlmResponse {
+            text,
+            usage: body.usage.map(|usage| TokenUsage {
+                input_tokens: usage.input_tokens,
+                output_tokens: usage.output_tokens,
+            }),
+        })
+    }
+
+    async fn complete_stream(
+        &self,
+        request: &LlmRequest,
+        on_text: &(dyn for<'t> Fn(&'t str) + Send + Sync),
+    ) -> Result<LlmResponse, LlmError> {
+        let response = self.send(request, true).await?;
+
+        let mut text = String::new();
+        let mut usage = TokenUsage::default();
+        // server-sent events, every `data:` line carries its event type as well
+        for_each_line(response, |line| {
+            let Some(data) = line.strip_prefix("data:") else {
+                return Ok(());
+            };
+            let event: Value = serde_json::from_str(data.trim())
+                .map_err(|e| LlmError::InvalidResponse(self.name(), e.to_string()))?;
+
+            match event["type"].as_str() {
+                Some("message_start") => {
+                    let tokens = &event["message"]["usage"]["input_tokens"];
+                    usage.input_tokens = tokens.as_u64().unwrap_or(0) as u32;
+                }
+                Some("content_block_delta") if event["delta"]["type"] == "text_delta" => {
+                    let delta = event["delta"]["text"].as_str().unwrap_or_default();
+                    on_text(delta);
+                    text.push_str(delta);
+                }
+                Some("message_delta") => {
+                    let tokens = &event["usage"]["output_tokens"];
+                    usage.output_tokens = tokens.as_u64().unwrap_or(0) as u32;
+                }
+                Some("error") => {
+                    let message = event["error"]["message"].as_str().unwrap_or("stream error");
+                    return Err(LlmError::InvalidResponse(self.name(), message.to_string()));
+                }
+                _ => {}
+            }
+            Ok(())
+        })
+        .await?;
+
+        Ok(LlmResponse {
+            text,
+            usage: Some(usage),
+        })
+    }
+}
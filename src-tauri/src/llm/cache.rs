@@ -0,0 +1,193 @@
+use crate::settings::SettingsManager;
+use crate::shortcut::Clip;
+use crate::AppState;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use tauri::State;
+
+/// TTL and size limits for cached LLM results, from the `llm_cache_*` settings
+#[derive(Debug, Clone)]
+pub struct CacheConfig {
+    pub enabled: bool,
+    pub ttl_days: u32,
+    pub max_entries: u32,
+}
+
+impl CacheConfig {
+    pub fn from_settings(settings: &SettingsManager) -> Self {
+        let number = |key: &str, default: u32| {
+            settings
+                .get_setting(key)
+                .and_then(|value| value.trim().parse::<u32>().ok())
+                .unwrap_or(default)
+        };
+
+        Self {
+            enabled: settings
+                .get_setting("llm_cache_enabled")
+                .is_none_or(|value| value == "true"),
+            ttl_days: number("llm_cache_ttl_days", 30),
+            max_entries: number("llm_cache_max_entries", 5000),
+        }
+    }
+}
+
+/// Everything a cached result depends on. A different prompt version is simply a miss
+pub struct CacheKey<'a> {
+    pub content_hash: String,
+    pub task: &'a str,
+    pub provider: &'a str,
+    pub model: &'a str,
+    pub prompt_version: &'a str,
+}
+
+pub fn content_hash(clip: &Clip) -> String {
+    let content = match clip {
+        Clip::Text { plain } => plain,
+        Clip::Image { data, .. } => data,
+    };
+    format!("{:x}", Sha256::digest(content.as_bytes()))
+}
+
+/// Hash of a clip together with the page fetched for it, the same URL can serve a different
+/// page next time. Starts with the clip's own hash so `forget_clip` still finds it
+pub fn page_content_hash(clip: &Clip, page_text: &str) -> String {
+    format!(
+        "{}:{:x}",
+        content_hash(clip),
+        Sha256::digest(page_text.as_bytes())
+    )
+}
+
+/// Short fingerprint of everything in a prompt that shapes the reply
+pub fn prompt_version(parts: &[&str]) -> String {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    format!("{:x}", hasher.finalize())[..12].to_string()
+}
+
+pub fn lookup<T: DeserializeOwned>(
+    db_path: &Path,
+    config: &CacheConfig,
+    key: &CacheKey,
+) -> Option<T> {
+    if !config.enabled {
+        return None;
+    }
+
+    let result = Connection::open(db_path).and_then(|conn| {
+        conn.query_row(
+            r#"
+            UPDATE llm_cache SET hits = hits + 1, last_used_at = CURRENT_TIMESTAMP
+            WHERE content_hash = ? AND task = ? AND provider = ? AND model = ? AND prompt_version = ?
+              AND created_at > datetime('now', ?)
+            RETURNING response
+            "#,
+            params![
+                key.content_hash,
+                key.task,
+                key.provider,
+                key.model,
+                key.prompt_version,
+                format!("-{} days", config.ttl_days)
+            ],
+            |row| row.get::<_, String>(0),
+        )
+        .optional()
+    });
+
+    match result {
+        Ok(Some(response)) => serde_json::from_str(&response).ok(),
+        Ok(None) => None,
+        Err(e) => {
+            eprintln!("LLM cache lookup failed: {}", e);
+            None
+        }
+    }
+}
+
+pub fn store<T: Serialize>(db_path: &Path, config: &CacheConfig, key: &CacheKey, value: &T) {
+    if !config.enabled {
+        return;
+    }
+
+    let result = Connection::open(db_path).and_then(|conn| {
+        let response = serde_json::to_string(value).unwrap_or_default();
+
+        conn.execute(
+            r#"
+            INSERT OR REPLACE INTO llm_cache (content_hash, task, provider, model, prompt_version, response)
+            VALUES (?, ?, ?, ?, ?, ?)
+            "#,
+            params![
+                key.content_hash,
+                key.task,
+                key.provider,
+                key.model,
+                key.prompt_version,
+                response
+            ],
+        )?;
+
+        // entries from an older prompt can't be hit again, they age out with the rest
+        conn.execute(
+            "DELETE FROM llm_cache WHERE created_at <= datetime('now', ?)",
+            params![format!("-{} days", config.ttl_days)],
+        )?;
+        // over the size limit the least recently used entries go first
+        conn.execute(
+            r#"
+            DELETE FROM llm_cache WHERE rowid IN (
+                SELECT rowid FROM llm_cache ORDER BY last_used_at DESC LIMIT -1 OFFSET ?
+            )
+            "#,
+            params![config.max_entries],
+        )?;
+
+        Ok(())
+    });
+
+    if let Err(e) = result {
+        eprintln!("Failed to store LLM result in cache: {}", e);
+    }
+}
+
+/// Drop every cached result for a clip's content, so nothing about it outlives the clip.
+/// Call before deleting the clip row
+pub fn forget_clip(conn: &Connection, clip_id: i64) -> rusqlite::Result<()> {
+    let clip_json: Option<String> = conn
+        .query_row(
+            "SELECT clip FROM clips WHERE id = ?",
+            params![clip_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+
+    let clip = clip_json
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .and_then(|value| Clip::from_stored(&value));
+
+    if let Some(clip) = clip {
+        conn.execute(
+            "DELETE FROM llm_cache WHERE content_hash = ?1 OR content_hash LIKE ?1 || ':%'",
+            params![content_hash(&clip)],
+        )?;
+    }
+    Ok(())
+}
+
+/// Empty the LLM result cache, returns how many entries were removed
+#[tauri::command]
+pub fn clear_llm_cache(state: State<'_, AppState>) -> Result<usize, String> {
+    let conn =
+        Connection::open(&state.db_path).map_err(|e| format!("Failed to open database: {e}"))?;
+
+    conn.execute("DELETE FROM llm_cache", [])
+        .map_err(|e| format!("Failed to clear cache: {e}"))
+}
@@ -0,0 +1,143 @@
+// a token is about four characters of ASCII text, but often just one of anything else
+const ASCII_CHARS_PER_TOKEN: usize = 4;
+
+/// Rough token count, erring on the high side for non-Latin scripts
+pub fn estimate_tokens(text: &str) -> usize {
+    let ascii = text.chars().filter(char::is_ascii).count();
+    let other = text.chars().count() - ascii;
+    ascii.div_ceil(ASCII_CHARS_PER_TOKEN) + other
+}
+
+/// Longest prefix of `text` that fits in `max_tokens`, always cut on a char boundary
+pub fn truncate_to_tokens(text: &str, max_tokens: usize) -> &str {
+    // counted in quarter tokens so ASCII and other characters can share one budget
+    let budget = max_tokens * ASCII_CHARS_PER_TOKEN;
+    let mut used = 0;
+
+    for (index, c) in text.char_indices() {
+        used += if c.is_ascii() {
+            1
+        } else {
+            ASCII_CHARS_PER_TOKEN
+        };
+        if used > budget {
+            return &text[..index];
+        }
+    }
+    text
+}
+
+/// Split text into pieces of at most `max_tokens`, breaking between paragraphs, lines,
+/// sentences or words where possible
+pub fn chunk_text(text: &str, max_tokens: usize) -> Vec<&str> {
+    let max_tokens = max_tokens.max(1);
+    let mut chunks = Vec::new();
+    let mut rest = text.trim();
+
+    while !rest.is_empty() {
+        let window = truncate_to_tokens(rest, max_tokens);
+        if window.len() == rest.len() {
+            chunks.push(rest);
+            break;
+        }
+
+        let cut = break_point(window).unwrap_or(window.len());
+        let (chunk, tail) = rest.split_at(cut);
+        if !chunk.trim().is_empty() {
+            chunks.push(chunk.trim());
+        }
+        rest = tail.trim_start();
+    }
+
+    chunks
+}
+
+fn break_point(window: &str) -> Option<usize> {
+    // a clean break isn't worth a chunk less than half full
+    let min = window.len() / 2;
+
+    ["\n\n", "\n", ". ", "? ", "! ", "; ", ", ", " "]
+        .iter()
+        .find_map(|separator| {
+            window
+                .rfind(separator)
+                .map(|index| index + separator.len())
+                .filter(|&index| index > min)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncates_multibyte_text_on_char_boundaries() {
+        let text = "日本語のテキスト😀🎉 mixed with ASCII";
+        for max_tokens in 0..12 {
+            let head = truncate_to_tokens(text, max_tokens);
+            assert!(text.starts_with(head));
+            assert!(estimate_tokens(head) <= max_tokens, "{max_tokens}: {head}");
+        }
+        assert_eq!(truncate_to_tokens("日本語", 2), "日本");
+        assert_eq!(truncate_to_tokens("😀😀", 1), "😀");
+    }
+
+    #[test]
+    fn zero_tokens_truncates_to_nothing() {
+        assert_eq!(truncate_to_tokens("hello", 0), "");
+        assert_eq!(truncate_to_tokens("", 0), "");
+    }
+
+    #[test]
+    fn chunks_cjk_and_emoji_within_budget() {
+        let text = "这是一个很长的中文句子，没有空格。".repeat(20) + &"🙂👍🏽🚀".repeat(30);
+        for max_tokens in [1, 2, 3, 7, 50] {
+            let chunks = chunk_text(&text, max_tokens);
+            assert!(!chunks.is_empty());
+            for chunk in &chunks {
+                assert!(
+                    estimate_tokens(chunk) <= max_tokens,
+                    "{max_tokens}: {chunk}"
+                );
+            }
+            assert_eq!(chunks.concat(), text.replace(' ', ""));
+        }
+    }
+
+    #[test]
+    fn breaks_between_paragraphs_first() {
+        let text = "First paragraph, with a comma. And a sentence.\n\nSecond one here";
+        let chunks = chunk_text(text, 14);
+        assert_eq!(chunks[0], "First paragraph, with a comma. And a sentence.");
+        assert_eq!(chunks[1], "Second one here");
+    }
+
+    #[test]
+    fn breaks_after_a_sentence_then_between_words() {
+        let chunks = chunk_text("One sentence here. Another sentence follows it", 6);
+        assert_eq!(chunks[0], "One sentence here.");
+
+        let chunks = chunk_text("alpha beta gamma delta epsilon zeta", 5);
+        assert_eq!(chunks, ["alpha beta gamma", "delta epsilon zeta"]);
+    }
+
+    #[test]
+    fn zero_tokens_still_makes_progress() {
+        let chunks = chunk_text("abc 日本", 0);
+        assert_eq!(chunks, ["abc", "日", "本"]);
+    }
+
+    #[test]
+    fn splits_a_word_without_break_points() {
+        let word = "x".repeat(100);
+        let chunks = chunk_text(&word, 5);
+        assert_eq!(chunks.len(), 5);
+        assert!(chunks.iter().all(|chunk| chunk.len() == 20));
+    }
+
+    #[test]
+    fn short_text_is_one_chunk() {
+        assert_eq!(chunk_text("  short text  ", 100), ["short text"]);
+        assert!(chunk_text("   ", 100).is_empty());
+    }
+}
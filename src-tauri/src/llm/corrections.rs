@@ -0,0 +1,178 @@
+use super::schema::{normalize_tags, CATEGORIES};
+use crate::secrets;
+use crate::shortcut::Clip;
+use rusqlite::{params, Connection};
+use std::collections::HashSet;
+use std::path::Path;
+
+// how much of a clip is kept as the example input
+const EXCERPT_CHARS: usize = 300;
+// only this many of the latest corrections are weighed for relevance
+const CANDIDATES: usize = 200;
+
+/// How the user filed a clip after the AI (or nobody) had a go at it
+#[derive(Debug, Clone)]
+pub struct Correction {
+    pub excerpt: String,
+    pub category: String,
+    pub tags: Vec<String>,
+}
+
+/// Remember a hand-set category and tags as a few-shot example for later analyses.
+/// Only text clips are kept, and never ones holding secrets
+pub fn record_correction(conn: &Connection, clip_id: i64) -> rusqlite::Result<()> {
+    let (clip_json, category, tags_json, sensitive, ai_status): (
+        String,
+        Option<String>,
+        Option<String>,
+        bool,
+        String,
+    ) = conn.query_row(
+        "SELECT clip, category, tags, sensitive, ai_status FROM clips WHERE id = ?",
+        params![clip_id],
+        |row| {
+            Ok((
+                row.get(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                row.get(3)?,
+                row.get(4)?,
+            ))
+        },
+    )?;
+
+    // examples end up in prompts, secrets must never get there this way either
+    if sensitive || ai_status == "skipped" {
+        return forget_clip(conn, clip_id);
+    }
+
+    let clip = serde_json::from_str(&clip_json)
+        .ok()
+        .and_then(|value| Clip::from_stored(&value));
+    let (Some(Clip::Text { plain }), Some(category)) = (clip, category) else {
+        return Ok(());
+    };
+    // hand-submitted clips are never scanned on the way in, and the whole text is checked
+    // since a secret cut off by the excerpt may no longer be recognizable
+    if !secrets::detect_secrets(&plain).is_empty() {
+        return forget_clip(conn, clip_id);
+    }
+
+    // the model can only answer with the prompt's categories, an example it can't follow
+    // would just confuse it
+    let category = category.trim().to_lowercase().replace([' ', '-'], "_");
+    if !CATEGORIES.contains(&category.as_str()) {
+        return forget_clip(conn, clip_id);
+    }
+
+    let excerpt: String = plain.trim().chars().take(EXCERPT_CHARS).collect();
+    if excerpt.is_empty() {
+        return Ok(());
+    }
+
+    let tags: Vec<String> = tags_json
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default();
+    let tags = normalize_tags(tags.iter().map(String::as_str));
+
+    conn.execute(
+        r#"
+        INSERT OR REPLACE INTO ai_corrections (clip_id, excerpt, category, tags)
+        VALUES (?, ?, ?, ?)
+        "#,
+        params![
+            clip_id,
+            excerpt,
+            category,
+            serde_json::to_string(&tags).unwrap_or_default()
+        ],
+    )?;
+
+    Ok(())
+}
+
+/// Drop the example taken from a clip. Call before deleting the clip row
+pub fn forget_clip(conn: &Connection, clip_id: i64) -> rusqlite::Result<()> {
+    conn.execute(
+        "DELETE FROM ai_corrections WHERE clip_id = ?",
+        params![clip_id],
+    )?;
+    Ok(())
+}
+
+/// The `limit` corrections most similar to `text`, newer ones first on a tie. Corrections
+/// sharing no words with it are left out
+pub fn relevant_examples(db_path: &Path, text: &str, limit: usize) -> Vec<Correction> {
+    if limit == 0 {
+        return Vec::new();
+    }
+
+    let result = Connection::open(db_path).and_then(|conn| {
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT ai_corrections.excerpt, ai_corrections.category, ai_corrections.tags
+            FROM ai_corrections
+            JOIN clips ON clips.id = ai_corrections.clip_id
+            WHERE clips.sensitive = 0 AND clips.ai_status != 'skipped'
+            ORDER BY ai_corrections.created_at DESC, ai_corrections.id DESC
+            LIMIT ?
+            "#,
+        )?;
+        let rows = stmt
+            .query_map(params![CANDIDATES as i64], |row| {
+                let tags: String = row.get(2)?;
+                Ok(Correction {
+                    excerpt: row.get(0)?,
+                    category: row.get(1)?,
+                    tags: serde_json::from_str(&tags).unwrap_or_default(),
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    });
+
+    let candidates = match result {
+        Ok(candidates) => candidates,
+        Err(e) => {
+            eprintln!("Failed to load AI corrections: {}", e);
+            return Vec::new();
+        }
+    };
+
+    let words = word_set(text);
+    let mut scored: Vec<(f64, usize, Correction)> = candidates
+        .into_iter()
+        .enumerate()
+        .map(|(age, correction)| {
+            (
+                similarity(&words, &word_set(&correction.excerpt)),
+                age,
+                correction,
+            )
+        })
+        .filter(|(score, _, _)| *score > 0.0)
+        .collect();
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
+
+    scored
+        .into_iter()
+        .take(limit)
+        .map(|(_, _, correction)| correction)
+        .collect()
+}
+
+fn word_set(text: &str) -> HashSet<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() >= 3)
+        .map(str::to_lowercase)
+        .collect()
+}
+
+// jaccard overlap of the two word sets
+fn similarity(a: &HashSet<String>, b: &HashSet<String>) -> f64 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(b).count() as f64 / union as f64
+}
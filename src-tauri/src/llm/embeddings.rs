@@ -0,0 +1,483 @@
+use super::chunking::truncate_to_tokens;
+use super::provider::{LlmError, TokenUsage};
+use super::resilience::RetryPolicy;
+use super::usage::{self, record_usage, UsageConfig, UsageRecord};
+use crate::pii::{PiiConfig, PiiRedactor};
+use crate::secrets;
+use crate::settings::{SettingsManager, SettingsManagerState};
+use crate::shortcut::Clip;
+use crate::AppState;
+use async_trait::async_trait;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+use serde::Deserialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tauri::State;
+use thiserror::Error;
+
+const OPENAI_BASE_URL: &str = "https://api.openai.com/v1";
+const OLLAMA_BASE_URL: &str = "http://localhost:11434";
+// embedding models read a few thousand tokens at most, the start of a clip says what it's about
+const MAX_INPUT_TOKENS: usize = 2000;
+// texts sent per request when embedding in bulk
+const BATCH_SIZE: usize = 32;
+
+/// Vectors for a batch of texts, in the order the texts were given
+pub struct Embeddings {
+    pub vectors: Vec<Vec<f32>>,
+    pub usage: Option<TokenUsage>,
+}
+
+/// A text embedding backend
+#[async_trait]
+pub trait EmbeddingProvider: Send + Sync {
+    /// Short provider id used in logs and usage records, e.g. `openai`
+    fn name(&self) -> &'static str;
+
+    fn model(&self) -> &str;
+
+    async fn embed(&self, texts: &[String]) -> Result<Embeddings, LlmError>;
+}
+
+/// OpenAI's `/embeddings` endpoint, or any server that speaks it
+pub struct OpenAiEmbeddings {
+    http: reqwest::Client,
+    name: &'static str,
+    model: String,
+    base_url: String,
+    api_key: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct OpenAiEmbeddingResponse {
+    data: Vec<OpenAiEmbedding>,
+    usage: Option<OpenAiEmbeddingUsage>,
+}
+
+#[derive(Deserialize)]
+struct OpenAiEmbedding {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+#[derive(Deserialize)]
+struct OpenAiEmbeddingUsage {
+    prompt_tokens: u32,
+}
+
+#[async_trait]
+impl EmbeddingProvider for OpenAiEmbeddings {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    async fn embed(&self, texts: &[String]) -> Result<Embeddings, LlmError> {
+        if self.name == "openai" && self.api_key.is_none() {
+            return Err(LlmError::MissingApiKey(self.name));
+        }
+
+        let mut request = self
+            .http
+            .post(format!("{}/embeddings", self.base_url))
+            .json(&json!({ "model": self.model, "input": texts }));
+        if let Some(api_key) = &self.api_key {
+            request = request.bearer_auth(api_key);
+        }
+
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            return Err(LlmError::from_response(self.name, response).await);
+        }
+
+        let mut body: OpenAiEmbeddingResponse = response
+            .json()
+            .await
+            .map_err(|e| LlmError::InvalidResponse(self.name, e.to_string()))?;
+        body.data.sort_by_key(|embedding| embedding.index);
+
+        Ok(Embeddings {
+            vectors: body
+                .data
+                .into_iter()
+                .map(|embedding| embedding.embedding)
+                .collect(),
+            usage: body.usage.map(|usage| TokenUsage {
+                input_tokens: usage.prompt_tokens,
+                output_tokens: 0,
+            }),
+        })
+    }
+}
+
+/// A local embedding model served by Ollama's `/api/embed`, runs on the CPU if need be
+pub struct OllamaEmbeddings {
+    http: reqwest::Client,
+    model: String,
+    base_url: String,
+}
+
+#[derive(Deserialize)]
+struct OllamaEmbedResponse {
+    embeddings: Vec<Vec<f32>>,
+    prompt_eval_count: Option<u32>,
+}
+
+#[async_trait]
+impl EmbeddingProvider for OllamaEmbeddings {
+    fn name(&self) -> &'static str {
+        "ollama"
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    async fn embed(&self, texts: &[String]) -> Result<Embeddings, LlmError> {
+        let response = self
+            .http
+            .post(format!("{}/api/embed", self.base_url))
+            .json(&json!({ "model": self.model, "input": texts }))
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(LlmError::from_response(self.name(), response).await);
+        }
+
+        let body: OllamaEmbedResponse = response
+            .json()
+            .await
+            .map_err(|e| LlmError::InvalidResponse(self.name(), e.to_string()))?;
+
+        Ok(Embeddings {
+            vectors: body.embeddings,
+            usage: body.prompt_eval_count.map(|input_tokens| TokenUsage {
+                input_tokens,
+                output_tokens: 0,
+            }),
+        })
+    }
+}
+
+/// The embedding backend picked by the `embedding_*` settings, `None` when there is none.
+/// `auto` follows `llm_provider` and its endpoint, Anthropic has no embeddings
+pub fn build_embedding_provider(settings: &SettingsManager) -> Option<Box<dyn EmbeddingProvider>> {
+    let non_empty = |key: &str| {
+        settings
+            .get_setting(key)
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+
+    let llm_provider = non_empty("llm_provider").unwrap_or_else(|| "openai".to_string());
+    let provider = match non_empty("embedding_provider").as_deref() {
+        None | Some("auto") => llm_provider.clone(),
+        Some(provider) => provider.to_string(),
+    };
+    // the chat endpoint is only a good guess when it is the same kind of server
+    let base_url = non_empty("embedding_base_url").or_else(|| {
+        (provider == llm_provider)
+            .then(|| non_empty("llm_base_url"))
+            .flatten()
+    });
+    let model = non_empty("embedding_model");
+    // the same per-request limit as chat requests, so a stalled server can't hold up a worker
+    let http = match reqwest::Client::builder()
+        .timeout(RetryPolicy::from_settings(settings).timeout)
+        .build()
+    {
+        Ok(http) => http,
+        Err(e) => {
+            eprintln!("Failed to set up embedding client: {}", e);
+            return None;
+        }
+    };
+
+    match provider.as_str() {
+        "openai" | "openai_compatible" => {
+            let (name, default_model) = if provider == "openai" {
+                ("openai", Some("text-embedding-3-small"))
+            } else {
+                ("openai_compatible", None)
+            };
+            let model = model.or_else(|| default_model.map(str::to_string))?;
+            let base_url = match (name, base_url) {
+                (_, Some(base_url)) => base_url,
+                ("openai", None) => OPENAI_BASE_URL.to_string(),
+                _ => return None,
+            };
+
+            Some(Box::new(OpenAiEmbeddings {
+                http,
+                name,
+                model,
+                base_url: base_url.trim_end_matches('/').to_string(),
+                api_key: non_empty("embedding_api_key").or_else(|| non_empty("llm_api_key")),
+            }))
+        }
+        "ollama" => Some(Box::new(OllamaEmbeddings {
+            http,
+            model: model.unwrap_or_else(|| "nomic-embed-text".to_string()),
+            base_url: base_url
+                .unwrap_or_else(|| OLLAMA_BASE_URL.to_string())
+                .trim_end_matches('/')
+                .to_string(),
+        })),
+        _ => None,
+    }
+}
+
+/// Embeds clips and queries, redacting personal data and counting usage like chat requests
+pub struct Embedder {
+    provider: Box<dyn EmbeddingProvider>,
+    pii: PiiConfig,
+    usage: UsageConfig,
+    db_path: PathBuf,
+}
+
+impl Embedder {
+    pub fn from_settings(settings: &SettingsManager, db_path: &Path) -> Option<Self> {
+        Some(Self {
+            provider: build_embedding_provider(settings)?,
+            pii: PiiConfig::from_settings(settings),
+            usage: UsageConfig::from_settings(settings),
+            db_path: db_path.to_path_buf(),
+        })
+    }
+
+    /// Stored vectors are only comparable with ones from the same model
+    pub fn model_id(&self) -> String {
+        format!("{}/{}", self.provider.name(), self.provider.model())
+    }
+
+    /// Unit length vectors for `texts`, so a dot product is their cosine similarity
+    pub async fn embed(&self, task: &str, texts: &[String]) -> Result<Vec<Vec<f32>>, LlmError> {
+        if let Some(limit) = usage::hard_limit_reached(&self.db_path, &self.usage) {
+            return Err(LlmError::BudgetExceeded(limit));
+        }
+
+        let texts: Vec<String> = texts
+            .iter()
+            .map(|text| PiiRedactor::new(&self.pii).redact(text))
+            .collect();
+
+        let started = Instant::now();
+        let result = self.provider.embed(&texts).await;
+
+        record_usage(
+            &self.db_path,
+            &self.usage,
+            &UsageRecord {
+                task,
+                provider: self.provider.name(),
+                model: self.provider.model(),
+                usage: result.as_ref().ok().and_then(|embeddings| embeddings.usage),
+                latency: started.elapsed(),
+                status: if result.is_ok() { "ok" } else { "error" },
+            },
+        );
+
+        let vectors = result?.vectors;
+        if vectors.len() != texts.len() {
+            return Err(LlmError::InvalidResponse(
+                self.provider.name(),
+                format!("{} embeddings for {} texts", vectors.len(), texts.len()),
+            ));
+        }
+        Ok(vectors.into_iter().map(normalize).collect())
+    }
+
+    /// Embed whichever of these clips, or every clip when `None`, have no up to date
+    /// embedding. Returns how many were embedded
+    pub async fn embed_clips(&self, clip_ids: Option<&[i64]>) -> Result<usize, EmbedError> {
+        let model = self.model_id();
+        let pending = pending_texts(&Connection::open(&self.db_path)?, &model, clip_ids)?;
+        let mut stored = 0;
+
+        for batch in pending.chunks(BATCH_SIZE) {
+            let texts: Vec<String> = batch.iter().map(|(_, text, _)| text.clone()).collect();
+            let vectors = self.embed("embed", &texts).await?;
+
+            let conn = Connection::open(&self.db_path)?;
+            for ((clip_id, _, text_hash), vector) in batch.iter().zip(vectors) {
+                conn.execute(
+                    r#"
+                    INSERT OR REPLACE INTO clip_embeddings (clip_id, model, dims, vector, text_hash)
+                    VALUES (?, ?, ?, ?, ?)
+                    "#,
+                    params![clip_id, model, vector.len(), to_blob(&vector), text_hash],
+                )?;
+                stored += 1;
+            }
+        }
+
+        Ok(stored)
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum EmbedError {
+    #[error("No embedding provider configured")]
+    NotConfigured,
+    #[error(transparent)]
+    Llm(#[from] LlmError),
+    #[error("Database error: {0}")]
+    Database(#[from] rusqlite::Error),
+}
+
+/// What gets embedded for a clip: its title and summary, then the start of its text.
+/// Images without either have nothing to go on
+pub fn embedding_text(clip: &Clip, title: Option<&str>, summary: Option<&str>) -> Option<String> {
+    let mut parts: Vec<&str> = [title, summary]
+        .into_iter()
+        .flatten()
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .collect();
+    if let Clip::Text { plain } = clip {
+        parts.push(plain.trim());
+    }
+
+    let text = parts.join("\n");
+    let text = truncate_to_tokens(&text, MAX_INPUT_TOKENS);
+    (!text.trim().is_empty()).then(|| text.to_string())
+}
+
+// (clip id, text, text hash) of clips whose embedding is missing, from another model or
+// made from text that has changed since. secrets are never embedded
+fn pending_texts(
+    conn: &Connection,
+    model: &str,
+    only: Option<&[i64]>,
+) -> rusqlite::Result<Vec<(i64, String, String)>> {
+    let existing: HashMap<i64, String> = {
+        let mut stmt =
+            conn.prepare("SELECT clip_id, text_hash FROM clip_embeddings WHERE model = ?")?;
+        let rows = stmt
+            .query_map(params![model], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<_, _>>()?;
+        rows
+    };
+
+    let mut sql =
+        "SELECT id, clip, title, summary FROM clips WHERE sensitive = 0 AND ai_status != 'skipped'"
+            .to_string();
+    if let Some(ids) = only {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        sql.push_str(&format!(" AND id IN ({})", vec!["?"; ids.len()].join(", ")));
+    }
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt
+        .query_map(params_from_iter(only.unwrap_or_default()), |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Option<String>>(2)?,
+                row.get::<_, Option<String>>(3)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(rows
+        .into_iter()
+        .filter_map(|(id, clip_json, title, summary)| {
+            let clip = serde_json::from_str(&clip_json)
+                .ok()
+                .and_then(|value| Clip::from_stored(&value))?;
+            let text = embedding_text(&clip, title.as_deref(), summary.as_deref())?;
+            // hand-submitted clips are never scanned on the way in
+            if !secrets::detect_secrets(&text).is_empty() {
+                return None;
+            }
+            let text_hash = format!("{:x}", Sha256::digest(text.as_bytes()));
+            (existing.get(&id) != Some(&text_hash)).then_some((id, text, text_hash))
+        })
+        .collect())
+}
+
+/// A clip's stored vector and the model that made it
+pub fn load_vector(
+    conn: &Connection,
+    clip_id: i64,
+) -> rusqlite::Result<Option<(String, Vec<f32>)>> {
+    conn.query_row(
+        "SELECT model, vector FROM clip_embeddings WHERE clip_id = ?",
+        params![clip_id],
+        |row| Ok((row.get(0)?, from_blob(&row.get::<_, Vec<u8>>(1)?))),
+    )
+    .optional()
+}
+
+/// Every stored vector made by `model`, by clip id
+pub fn load_vectors(conn: &Connection, model: &str) -> rusqlite::Result<Vec<(i64, Vec<f32>)>> {
+    let mut stmt = conn.prepare("SELECT clip_id, vector FROM clip_embeddings WHERE model = ?")?;
+    let rows = stmt
+        .query_map(params![model], |row| {
+            Ok((row.get(0)?, from_blob(&row.get::<_, Vec<u8>>(1)?)))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(rows)
+}
+
+/// Cosine similarity of two unit length vectors
+pub fn similarity(a: &[f32], b: &[f32]) -> f64 {
+    a.iter()
+        .zip(b)
+        .map(|(x, y)| (*x as f64) * (*y as f64))
+        .sum()
+}
+
+fn normalize(vector: Vec<f32>) -> Vec<f32> {
+    let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm == 0.0 {
+        return vector;
+    }
+    vector.into_iter().map(|x| x / norm).collect()
+}
+
+// little endian f32s, four bytes each
+fn to_blob(vector: &[f32]) -> Vec<u8> {
+    vector.iter().flat_map(|x| x.to_le_bytes()).collect()
+}
+
+fn from_blob(blob: &[u8]) -> Vec<f32> {
+    blob.chunks_exact(4)
+        .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .collect()
+}
+
+/// Drop a clip's embedding. Call before deleting the clip row
+pub fn forget_clip(conn: &Connection, clip_id: i64) -> rusqlite::Result<()> {
+    conn.execute(
+        "DELETE FROM clip_embeddings WHERE clip_id = ?",
+        params![clip_id],
+    )?;
+    Ok(())
+}
+
+/// Embed every clip that has no up to date embedding, e.g. after switching models.
+/// Returns how many were embedded
+#[tauri::command]
+pub async fn build_embeddings(
+    state: State<'_, AppState>,
+    settings: State<'_, SettingsManagerState>,
+) -> Result<usize, String> {
+    let embedder = Embedder::from_settings(&settings.0, &state.db_path)
+        .ok_or_else(|| EmbedError::NotConfigured.to_string())?;
+
+    let count = embedder
+        .embed_clips(None)
+        .await
+        .map_err(|e| format!("Failed to build embeddings: {e}"))?;
+    println!("Embedded {} clip(s) with {}", count, embedder.model_id());
+
+    Ok(count)
+}
@@ -0,0 +1,89 @@
+use crate::AppState;
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use std::path::Path;
+use tauri::State;
+
+/// One field of an LLM result that had to be replaced with a default
+pub struct Fallback<'a> {
+    pub task: &'a str,
+    pub field: &'a str,
+    pub reason: &'a str,
+    pub detail: Option<&'a str>,
+    pub provider: &'a str,
+    pub model: &'a str,
+}
+
+/// Store a fallback so we can tell how often the model lets us down, and why
+pub fn record_fallback(db_path: &Path, fallback: &Fallback) {
+    let result = Connection::open(db_path).and_then(|conn| {
+        conn.execute(
+            "INSERT INTO llm_fallbacks (task, field, reason, detail, provider, model) VALUES (?, ?, ?, ?, ?, ?)",
+            params![
+                fallback.task,
+                fallback.field,
+                fallback.reason,
+                fallback.detail,
+                fallback.provider,
+                fallback.model
+            ],
+        )
+    });
+
+    if let Err(e) = result {
+        eprintln!("Failed to record LLM fallback: {}", e);
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct FallbackStat {
+    pub task: String,
+    pub field: String,
+    pub reason: String,
+    pub provider: String,
+    pub model: String,
+    pub count: i64,
+    pub last_seen: String,
+}
+
+/// How many fallbacks happened in the last `days` days (default 30), grouped by cause
+#[tauri::command]
+pub async fn get_llm_fallback_stats(
+    state: State<'_, AppState>,
+    days: Option<u32>,
+) -> Result<Vec<FallbackStat>, String> {
+    let conn =
+        Connection::open(&state.db_path).map_err(|e| format!("Failed to open database: {e}"))?;
+
+    let mut stmt = conn
+        .prepare(
+            r#"
+        SELECT task, field, reason, provider, model, COUNT(*), MAX(created_at)
+        FROM llm_fallbacks
+        WHERE created_at >= datetime('now', ?)
+        GROUP BY task, field, reason, provider, model
+        ORDER BY COUNT(*) DESC
+        "#,
+        )
+        .map_err(|e| format!("Failed to prepare statement: {e}"))?;
+
+    let window = format!("-{} days", days.unwrap_or(30));
+
+    let stats = stmt
+        .query_map(params![window], |row| {
+            Ok(FallbackStat {
+                task: row.get(0)?,
+                field: row.get(1)?,
+                reason: row.get(2)?,
+                provider: row.get(3)?,
+                model: row.get(4)?,
+                count: row.get(5)?,
+                last_seen: row.get(6)?,
+            })
+        })
+        .map_err(|e| format!("Failed to execute query: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to process row: {e}"))?;
+
+    Ok(stats)
+}
@@ -0,0 +1,122 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Emitter};
+use tokio_util::sync::CancellationToken;
+
+/// Cancellation handles for every AI request currently running, by job id
+#[derive(Default)]
+pub struct InFlightJobs {
+    tokens: Mutex<HashMap<i64, CancellationToken>>,
+}
+
+pub struct InFlightJobsState(pub Arc<InFlightJobs>);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AiJobEvent {
+    pub job_id: i64,
+    pub clip_id: i64,
+    pub kind: &'static str,
+}
+
+/// A piece of text a job's model reply has streamed so far
+#[derive(Debug, Clone, Serialize)]
+pub struct AiStreamEvent {
+    pub job_id: i64,
+    pub clip_id: i64,
+    pub delta: String,
+}
+
+/// A registered job, removed from the registry again when dropped
+pub struct JobHandle {
+    jobs: Arc<InFlightJobs>,
+    app_handle: AppHandle,
+    pub id: i64,
+    pub clip_id: i64,
+    pub kind: &'static str,
+    pub token: CancellationToken,
+}
+
+impl InFlightJobs {
+    /// Register a job and tell the frontend about it so it can be cancelled
+    pub fn start(
+        self: &Arc<Self>,
+        app_handle: &AppHandle,
+        id: i64,
+        clip_id: i64,
+        kind: &'static str,
+    ) -> JobHandle {
+        let token = CancellationToken::new();
+        self.tokens.lock().unwrap().insert(id, token.clone());
+
+        let _ = app_handle.emit(
+            "ai-job-started",
+            AiJobEvent {
+                job_id: id,
+                clip_id,
+                kind,
+            },
+        );
+
+        JobHandle {
+            jobs: self.clone(),
+            app_handle: app_handle.clone(),
+            id,
+            clip_id,
+            kind,
+            token,
+        }
+    }
+
+    pub fn cancel(&self, id: i64) -> bool {
+        match self.tokens.lock().unwrap().get(&id) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn cancel_all(&self) -> usize {
+        let tokens = self.tokens.lock().unwrap();
+        for token in tokens.values() {
+            token.cancel();
+        }
+        tokens.len()
+    }
+}
+
+impl JobHandle {
+    /// Emits whatever it is given as `ai-stream` events for this job
+    pub fn stream_sink(&self) -> Arc<dyn Fn(&str) + Send + Sync> {
+        let app_handle = self.app_handle.clone();
+        let (job_id, clip_id) = (self.id, self.clip_id);
+
+        Arc::new(move |delta: &str| {
+            let _ = app_handle.emit(
+                "ai-stream",
+                AiStreamEvent {
+                    job_id,
+                    clip_id,
+                    delta: delta.to_string(),
+                },
+            );
+        })
+    }
+}
+
+impl Drop for JobHandle {
+    fn drop(&mut self) {
+        self.jobs.tokens.lock().unwrap().remove(&self.id);
+
+        let _ = self.app_handle.emit(
+            "ai-job-finished",
+            AiJobEvent {
+                job_id: self.id,
+                clip_id: self.clip_id,
+                kind: self.kind,
+            },
+        );
+    }
+}
@@ -0,0 +1,716 @@
+mod anthropic;
+pub mod cache;
+pub mod chunking;
+pub mod corrections;
+pub mod embeddings;
+pub mod fallbacks;
+pub mod jobs;
+mod ollama;
+mod openai;
+pub mod prompts;
+mod provider;
+pub mod resilience;
+mod schema;
+pub mod usage;
+
+pub use provider::{
+    build_provider, LlmConfig, LlmError, LlmProvider, LlmRequest, LlmResponse, Role,
+};
+
+use crate::classify::{self, Classification};
+use crate::extract::PageContent;
+use crate::pii::{self, PiiConfig, PiiRedactor};
+use crate::settings::SettingsManager;
+use crate::shortcut::{is_url, Clip};
+use cache::{CacheConfig, CacheKey};
+use chunking::{chunk_text, estimate_tokens, truncate_to_tokens};
+use corrections::Correction;
+use fallbacks::{record_fallback, Fallback};
+use prompts::PromptTemplates;
+use schema::{analysis_schema, partial_string_field, validate_analysis};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio_util::sync::CancellationToken;
+use usage::{record_usage, UsageConfig, UsageRecord};
+
+const NO_SUMMARY: &str = "No summary available";
+
+// how many times a reply that fails validation is sent back to the model for a fix
+const MAX_VALIDATION_RETRIES: usize = 1;
+
+const CHUNK_SYSTEM_PROMPT: &str =
+    "You summarize parts of long documents so they can be combined into one summary later.";
+// rounds of summarizing summaries before whatever is left gets cut to fit
+const MAX_REDUCE_ROUNDS: usize = 3;
+// longest answer to a question about the library
+const ANSWER_TOKENS: u32 = 800;
+
+/// Everything the LLM fills in for a clip, from a single request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClipAnalysis {
+    pub category: String,
+    pub tags: Vec<String>,
+    pub title: String,
+    pub summary: String,
+}
+
+impl ClipAnalysis {
+    /// What a clip gets when the LLM can't be reached at all, from the offline classifier
+    pub fn fallback(clip: &Clip, want_summary: bool) -> Self {
+        let Classification { category, tags } = classify::classify(clip);
+
+        Self {
+            category: category.to_string(),
+            tags,
+            title: fallback_title(clip),
+            summary: fallback_summary(want_summary),
+        }
+    }
+}
+
+/// How much context goes into one analysis, from the `llm_few_shot_examples`,
+/// `llm_chunk_tokens` and `llm_max_chunks` settings
+#[derive(Debug, Clone)]
+pub struct AnalysisConfig {
+    pub few_shot_examples: usize,
+    /// longest text sent in one request, longer text is summarized in parts
+    pub chunk_tokens: usize,
+    /// parts of a long text summarized at most, the rest is left out
+    pub max_chunks: usize,
+}
+
+impl AnalysisConfig {
+    pub fn from_settings(settings: &SettingsManager) -> Self {
+        let number = |key: &str, default: usize| {
+            settings
+                .get_setting(key)
+                .and_then(|value| value.trim().parse::<usize>().ok())
+                .unwrap_or(default)
+        };
+
+        Self {
+            few_shot_examples: number("llm_few_shot_examples", 3).min(10),
+            chunk_tokens: number("llm_chunk_tokens", 1500).max(500),
+            max_chunks: number("llm_max_chunks", 20).max(1),
+        }
+    }
+}
+
+/// The configured model plus everything that shapes what gets sent to it
+pub struct LlmClient {
+    provider: Box<dyn LlmProvider>,
+    pii: PiiConfig,
+    cache: CacheConfig,
+    usage: UsageConfig,
+    analysis: AnalysisConfig,
+    prompts: PromptTemplates,
+    db_path: PathBuf,
+    cancel: CancellationToken,
+    on_summary: Option<SummarySink>,
+}
+
+/// Receives a summary piece by piece while the model is still writing it
+pub type SummarySink = Arc<dyn Fn(&str) + Send + Sync>;
+
+impl LlmClient {
+    pub fn new(
+        provider: Box<dyn LlmProvider>,
+        pii: PiiConfig,
+        cache: CacheConfig,
+        usage: UsageConfig,
+        analysis: AnalysisConfig,
+        prompts: PromptTemplates,
+        db_path: PathBuf,
+    ) -> Self {
+        Self {
+            provider,
+            pii,
+            cache,
+            usage,
+            analysis,
+            prompts,
+            db_path,
+            cancel: CancellationToken::new(),
+            on_summary: None,
+        }
+    }
+
+    pub fn from_settings(settings: &SettingsManager, db_path: &Path) -> Self {
+        Self::new(
+            build_provider(&LlmConfig::from_settings(settings)),
+            PiiConfig::from_settings(settings),
+            CacheConfig::from_settings(settings),
+            UsageConfig::from_settings(settings),
+            AnalysisConfig::from_settings(settings),
+            PromptTemplates::load(db_path, settings),
+            db_path.to_path_buf(),
+        )
+    }
+
+    /// Abort any request this client is making as soon as `token` is cancelled
+    pub fn with_cancel(mut self, token: CancellationToken) -> Self {
+        self.cancel = token;
+        self
+    }
+
+    /// Stream the summary of each analysis into `sink` as it is written
+    pub fn with_summary_stream(mut self, sink: SummarySink) -> Self {
+        self.on_summary = Some(sink);
+        self
+    }
+
+    /// Send one request, recording its tokens, latency and cost under `task`
+    async fn complete(&self, task: &str, request: &LlmRequest) -> Result<LlmResponse, LlmError> {
+        self.track(task, self.provider.complete(request)).await
+    }
+
+    /// Send one request, handing the reply to `on_text` as it streams in. Recorded like `complete`
+    async fn complete_stream(
+        &self,
+        task: &str,
+        request: &LlmRequest,
+        on_text: &(dyn Fn(&str) + Send + Sync),
+    ) -> Result<LlmResponse, LlmError> {
+        self.track(task, self.provider.complete_stream(request, on_text))
+            .await
+    }
+
+    async fn track(
+        &self,
+        task: &str,
+        call: impl Future<Output = Result<LlmResponse, LlmError>>,
+    ) -> Result<LlmResponse, LlmError> {
+        // past the hard limit everything falls back to offline categorization
+        if let Some(limit) = usage::hard_limit_reached(&self.db_path, &self.usage) {
+            return Err(LlmError::BudgetExceeded(limit));
+        }
+
+        let started = Instant::now();
+        let result = tokio::select! {
+            _ = self.cancel.cancelled() => Err(LlmError::Cancelled),
+            result = call => result,
+        };
+
+        record_usage(
+            &self.db_path,
+            &self.usage,
+            &UsageRecord {
+                task,
+                provider: self.provider.name(),
+                model: self.provider.model(),
+                usage: result.as_ref().ok().and_then(|response| response.usage),
+                latency: started.elapsed(),
+                status: match &result {
+                    Ok(_) => "ok",
+                    Err(LlmError::Cancelled) => "cancelled",
+                    Err(_) => "error",
+                },
+            },
+        );
+
+        result
+    }
+
+    /// Categorize, tag, title and optionally summarize a clip in one round trip. For a URL
+    /// clip `page` is what was fetched from it, without one only the URL is analyzed
+    pub async fn analyze_clip(
+        &self,
+        clip: &Clip,
+        want_summary: bool,
+        page: Option<&PageContent>,
+    ) -> Result<ClipAnalysis, LlmError> {
+        let template = match (clip, page) {
+            (Clip::Text { .. }, Some(_)) => prompts::ANALYZE_PAGE,
+            (Clip::Text { .. }, None) => prompts::ANALYZE_TEXT,
+            (Clip::Image { .. }, _) => prompts::ANALYZE_IMAGE,
+        };
+        let summary_instruction = self.summary_instruction(clip, want_summary, page);
+
+        let mut redactor = PiiRedactor::new(&self.pii);
+        // the examples change as the user corrects clips, older replies then simply miss
+        let examples = match clip {
+            Clip::Text { plain } => {
+                let examples = corrections::relevant_examples(
+                    &self.db_path,
+                    plain,
+                    self.analysis.few_shot_examples,
+                );
+                few_shot_section(&examples, &mut redactor)
+            }
+            Clip::Image { .. } => String::new(),
+        };
+
+        let prompt_version = self.prompts.version(
+            &[prompts::ANALYZE_SYSTEM, template],
+            &[
+                &summary_instruction,
+                &examples,
+                &analysis_schema().to_string(),
+            ],
+        );
+        let key = CacheKey {
+            content_hash: match page {
+                Some(page) => cache::page_content_hash(clip, &page.text),
+                None => cache::content_hash(clip),
+            },
+            // with and without a summary are different replies to the same content
+            task: match (page.is_some(), want_summary) {
+                (false, false) => "analyze",
+                (false, true) => "analyze_summary",
+                (true, false) => "analyze_page",
+                (true, true) => "analyze_page_summary",
+            },
+            provider: self.provider.name(),
+            model: self.provider.model(),
+            prompt_version: &prompt_version,
+        };
+
+        if let Some(analysis) = cache::lookup::<ClipAnalysis>(&self.db_path, &self.cache, &key) {
+            println!("Using cached analysis: {}", analysis.category);
+            return Ok(analysis);
+        }
+
+        match self
+            .request_analysis(
+                clip,
+                want_summary,
+                page,
+                &summary_instruction,
+                &examples,
+                redactor,
+            )
+            .await
+        {
+            Ok((analysis, complete)) => {
+                // results patched up with fallbacks shouldn't stick around
+                if complete {
+                    cache::store(&self.db_path, &self.cache, &key, &analysis);
+                }
+                Ok(analysis)
+            }
+            Err(e) => {
+                self.record_fallback("all", e.reason(), Some(&e.to_string()));
+                Err(e)
+            }
+        }
+    }
+
+    async fn request_analysis(
+        &self,
+        clip: &Clip,
+        want_summary: bool,
+        page: Option<&PageContent>,
+        summary_instruction: &str,
+        examples: &str,
+        mut redactor: PiiRedactor,
+    ) -> Result<(ClipAnalysis, bool), LlmError> {
+        let system = self.prompts.render(prompts::ANALYZE_SYSTEM, &[]);
+
+        let request = match (clip, page) {
+            (Clip::Text { plain }, Some(page)) => {
+                let (content, max_output_tokens) = self
+                    .fit_content(&page.text, want_summary, &mut redactor)
+                    .await?;
+                let optional = |value: &Option<String>, redactor: &mut PiiRedactor| {
+                    value
+                        .as_deref()
+                        .map(|value| redactor.redact(value))
+                        .unwrap_or_else(|| "(none)".to_string())
+                };
+                let title = optional(&page.title, &mut redactor);
+                let description = optional(&page.description, &mut redactor);
+
+                let user_prompt = self.prompts.render(
+                    prompts::ANALYZE_PAGE,
+                    &[
+                        ("url", &redactor.redact(plain.trim())),
+                        ("title", &title),
+                        ("description", &description),
+                        ("content", &content),
+                        ("summary_instruction", summary_instruction),
+                        ("examples", examples),
+                    ],
+                );
+
+                LlmRequest::new(system, max_output_tokens).user_text(user_prompt)
+            }
+            (Clip::Text { plain }, None) => {
+                let (content, max_output_tokens) =
+                    self.fit_content(plain, want_summary, &mut redactor).await?;
+
+                let user_prompt = self.prompts.render(
+                    prompts::ANALYZE_TEXT,
+                    &[
+                        ("content", &content),
+                        ("summary_instruction", summary_instruction),
+                        ("examples", examples),
+                    ],
+                );
+
+                LlmRequest::new(system, max_output_tokens).user_text(user_prompt)
+            }
+            (
+                Clip::Image {
+                    data,
+                    width,
+                    height,
+                },
+                _,
+            ) => {
+                let user_prompt = self.prompts.render(
+                    prompts::ANALYZE_IMAGE,
+                    &[
+                        ("width", &width.to_string()),
+                        ("height", &height.to_string()),
+                        ("summary_instruction", summary_instruction),
+                    ],
+                );
+
+                LlmRequest::new(system, 400)
+                    .user_text(user_prompt)
+                    .user_image(data.clone())
+            }
+        };
+
+        let mut request = request.json_schema("clip_analysis", analysis_schema());
+        let mut attempt = 0;
+
+        let validated = loop {
+            // only the first reply streams, corrections replace it once stored anyway
+            let response = match &self.on_summary {
+                Some(sink) if want_summary && attempt == 0 => {
+                    self.stream_analysis(&request, &redactor, sink.as_ref())
+                        .await?
+                }
+                _ => self.complete("analyze", &request).await?,
+            };
+
+            // placeholders are swapped back before tags get normalized and lose their brackets
+            let validated = validate_analysis(&redactor.restore(&response.text), want_summary);
+
+            if validated.issues().is_empty() || attempt == MAX_VALIDATION_RETRIES {
+                break validated;
+            }
+            attempt += 1;
+
+            let problems = validated.describe_issues();
+            eprintln!("LLM analysis failed validation, retrying: {}", problems);
+
+            request = request.assistant_text(response.text).user_text(format!(
+                "Your previous reply was invalid: {}. Reply again with only the corrected JSON object.",
+                problems
+            ));
+        };
+
+        let issues = validated.issues();
+        for (field, issue) in &issues {
+            self.record_fallback(field, issue.reason(), issue.detail());
+        }
+        let complete = issues.is_empty();
+
+        // only worth classifying locally if the model got one of these wrong
+        let local = || classify::classify(clip);
+        let analysis = ClipAnalysis {
+            category: validated
+                .category
+                .unwrap_or_else(|_| local().category.to_string()),
+            tags: validated.tags.unwrap_or_else(|_| local().tags),
+            title: validated.title.unwrap_or_else(|_| fallback_title(clip)),
+            summary: validated
+                .summary
+                .unwrap_or_else(|_| fallback_summary(want_summary)),
+        };
+
+        println!(
+            "LLM analyzed clip as: {} with tags: {:?}",
+            analysis.category, analysis.tags
+        );
+
+        Ok((analysis, complete))
+    }
+
+    fn summary_instruction(
+        &self,
+        clip: &Clip,
+        want_summary: bool,
+        page: Option<&PageContent>,
+    ) -> String {
+        if !want_summary {
+            return "Do not summarize, leave \"summary\" empty.".to_string();
+        }
+
+        // the summary asked for depends on what the content looks like
+        let category = match page {
+            Some(page) => {
+                classify::classify(&Clip::Text {
+                    plain: page.text.clone(),
+                })
+                .category
+            }
+            None => classify::classify(clip).category,
+        };
+        self.prompts.summary_instruction(category).to_string()
+    }
+
+    /// Redacted text for the prompt, with the output tokens the reply needs. Text that's
+    /// too long for one request is summarized in parts, or cut short if no summary is wanted
+    async fn fit_content(
+        &self,
+        text: &str,
+        want_summary: bool,
+        redactor: &mut PiiRedactor,
+    ) -> Result<(String, u32), LlmError> {
+        let tokens = estimate_tokens(text);
+        let max_tokens = self.analysis.chunk_tokens;
+
+        if tokens <= max_tokens {
+            Ok((redactor.redact(text), 400))
+        } else if want_summary {
+            // too long for one request: summarize it in parts, then analyze the summaries
+            let summaries = self.summarize_in_parts(text, redactor).await?;
+            Ok((summaries, 700))
+        } else {
+            // the beginning is plenty to categorize by
+            let head = truncate_to_tokens(text, max_tokens);
+            let omitted = tokens.saturating_sub(estimate_tokens(head));
+            let content = format!(
+                "{}\n[... about {} more tokens not shown]",
+                redactor.redact(head),
+                omitted
+            );
+            Ok((content, 400))
+        }
+    }
+
+    /// Map-reduce over a long text: summarize each part, then the summaries, until they fit
+    /// into one request. Placeholders for personal data stay in until the final reply
+    async fn summarize_in_parts(
+        &self,
+        text: &str,
+        redactor: &mut PiiRedactor,
+    ) -> Result<String, LlmError> {
+        let max_tokens = self.analysis.chunk_tokens;
+        // each round shrinks the text about four times over
+        let output_tokens = (max_tokens / 4).clamp(100, 400) as u32;
+
+        let mut parts: Vec<String> = chunk_text(text, max_tokens)
+            .into_iter()
+            .map(|part| redactor.redact(part))
+            .collect();
+
+        let mut omitted = 0;
+        if parts.len() > self.analysis.max_chunks {
+            omitted = parts.len() - self.analysis.max_chunks;
+            parts.truncate(self.analysis.max_chunks);
+        }
+        println!(
+            "Summarizing long text in {} part(s), {} left out",
+            parts.len(),
+            omitted
+        );
+
+        for _ in 0..MAX_REDUCE_ROUNDS {
+            let count = parts.len();
+            let mut summaries = Vec::with_capacity(count);
+
+            for (index, part) in parts.iter().enumerate() {
+                let prompt = self.prompts.render(
+                    prompts::SUMMARIZE_CHUNK,
+                    &[
+                        ("content", part),
+                        ("part", &(index + 1).to_string()),
+                        ("parts", &count.to_string()),
+                    ],
+                );
+                let request = LlmRequest::new(CHUNK_SYSTEM_PROMPT, output_tokens).user_text(prompt);
+                let response = self.complete("summarize_chunk", &request).await?;
+
+                summaries.push(format!(
+                    "Part {} of {}:\n{}",
+                    index + 1,
+                    count,
+                    response.text.trim()
+                ));
+            }
+
+            let combined = summaries.join("\n\n");
+            if count == 1 || estimate_tokens(&combined) <= max_tokens {
+                return Ok(with_omitted_note(combined, omitted));
+            }
+
+            parts = chunk_text(&combined, max_tokens)
+                .into_iter()
+                .map(str::to_string)
+                .collect();
+        }
+
+        // still too long after every round, keep what fits
+        let combined = truncate_to_tokens(&parts.join("\n\n"), max_tokens).to_string();
+        Ok(with_omitted_note(combined, omitted))
+    }
+
+    // the reply is a JSON object, only the summary inside it goes to `sink`
+    async fn stream_analysis(
+        &self,
+        request: &LlmRequest,
+        redactor: &PiiRedactor,
+        sink: &(dyn Fn(&str) + Send + Sync),
+    ) -> Result<LlmResponse, LlmError> {
+        // (reply so far, how much of its summary went out)
+        let streamed = Mutex::new((String::new(), 0));
+        let forward = |delta: &str| {
+            let mut streamed = streamed.lock().unwrap();
+            let (text, sent) = &mut *streamed;
+            text.push_str(delta);
+
+            let Some(summary) = partial_string_field(text, "summary") else {
+                return;
+            };
+            let end = pii::restorable_len(&summary);
+            if end > *sent {
+                sink(&redactor.restore(&summary[*sent..end]));
+                *sent = end;
+            }
+        };
+        let response = self.complete_stream("analyze", request, &forward).await?;
+
+        let (text, sent) = &*streamed.lock().unwrap();
+        if let Some(summary) = partial_string_field(text, "summary") {
+            if *sent < summary.len() {
+                sink(&redactor.restore(&summary[*sent..]));
+            }
+        }
+
+        Ok(response)
+    }
+
+    /// Answer a question from `sources` alone, citing clips as `[clip:<id>]`. `history` is the
+    /// conversation so far, oldest first. The answer streams into `on_text` with personal
+    /// data already put back, the whole of it is returned at the end
+    pub async fn answer_question(
+        &self,
+        history: &[(Role, String)],
+        sources: &str,
+        question: &str,
+        on_text: &(dyn Fn(&str) + Send + Sync),
+    ) -> Result<String, LlmError> {
+        let mut redactor = PiiRedactor::new(&self.pii);
+
+        let system = self.prompts.render(prompts::ASK_SYSTEM, &[]);
+        let mut request = LlmRequest::new(system, ANSWER_TOKENS);
+        for (role, text) in history {
+            let text = redactor.redact(text);
+            request = match role {
+                Role::User => request.user_text(text),
+                Role::Assistant => request.assistant_text(text),
+            };
+        }
+        let prompt = self.prompts.render(
+            prompts::ASK_QUESTION,
+            &[
+                ("sources", &redactor.redact(sources)),
+                ("question", &redactor.redact(question)),
+            ],
+        );
+        let request = request.user_text(prompt);
+
+        // (text so far, how much of it went out) so placeholders are only restored whole
+        let streamed = Mutex::new((String::new(), 0));
+        let forward = |delta: &str| {
+            let mut streamed = streamed.lock().unwrap();
+            let (text, sent) = &mut *streamed;
+            text.push_str(delta);
+
+            let end = pii::restorable_len(text);
+            if end > *sent {
+                on_text(&redactor.restore(&text[*sent..end]));
+                *sent = end;
+            }
+        };
+        let response = self.complete_stream("ask", &request, &forward).await?;
+
+        let (text, sent) = &*streamed.lock().unwrap();
+        if *sent < text.len() {
+            on_text(&redactor.restore(&text[*sent..]));
+        }
+
+        Ok(redactor.restore(&response.text))
+    }
+
+    fn record_fallback(&self, field: &str, reason: &str, detail: Option<&str>) {
+        eprintln!("LLM fallback for {}: {} {:?}", field, reason, detail);
+
+        record_fallback(
+            &self.db_path,
+            &Fallback {
+                task: "analyze",
+                field,
+                reason,
+                detail,
+                provider: self.provider.name(),
+                model: self.provider.model(),
+            },
+        );
+    }
+}
+
+fn with_omitted_note(summaries: String, omitted_parts: usize) -> String {
+    if omitted_parts == 0 {
+        return summaries;
+    }
+    format!(
+        "{}\n\n[{} later part(s) of the document were not summarized]",
+        summaries, omitted_parts
+    )
+}
+
+/// Past corrections as examples of how this user files things, empty when there are none
+fn few_shot_section(examples: &[Correction], redactor: &mut PiiRedactor) -> String {
+    if examples.is_empty() {
+        return String::new();
+    }
+
+    let mut section = String::from(
+        "\n\nThe user corrected these earlier results, file similar content the same way:",
+    );
+    for example in examples {
+        let input = serde_json::to_string(&redactor.redact(&example.excerpt)).unwrap_or_default();
+        let output = serde_json::json!({ "category": example.category, "tags": example.tags });
+        section.push_str(&format!("\nInput: {}\nOutput: {}", input, output));
+    }
+    section
+}
+
+fn fallback_title(clip: &Clip) -> String {
+    match clip {
+        Clip::Text { plain } => {
+            let trimmed = plain.trim();
+
+            if is_url(trimmed) {
+                if let Ok(url) = url::Url::parse(trimmed) {
+                    let host = url.host_str().unwrap_or_default();
+                    return format!("{}{}", host, url.path().trim_end_matches('/'));
+                }
+            }
+
+            let first_line = trimmed.lines().next().unwrap_or_default();
+            if first_line.chars().count() > 60 {
+                format!("{}...", first_line.chars().take(60).collect::<String>())
+            } else {
+                first_line.to_string()
+            }
+        }
+        Clip::Image { width, height, .. } => format!("Image {}x{}", width, height),
+    }
+}
+
+fn fallback_summary(want_summary: bool) -> String {
+    if want_summary {
+        NO_SUMMARY.to_string()
+    } else {
+        String::new()
+    }
+}
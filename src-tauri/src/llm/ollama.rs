@@ -0,0 +1,174 @@
+use super::provider::{
+    for_each_line, LlmError, LlmProvider, LlmRequest, LlmResponse, Part, Role, TokenUsage,
+};
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+const DEFAULT_BASE_URL: &str = "http://localhost:11434";
+
+/// A local Ollama server through its native `/api/chat` endpoint
+pub struct OllamaProvider {
+    http: reqwest::Client,
+    model: String,
+    base_url: String,
+}
+
+impl OllamaProvider {
+    pub fn new(model: String, base_url: Option<String>) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            model,
+            base_url: base_url
+                .unwrap_or_else(|| DEFAULT_BASE_URL.to_string())
+                .trim_end_matches('/')
+                .to_string(),
+        }
+    }
+
+    fn body(&self, request: &LlmRequest, stream: bool) -> Value {
+        let mut messages = vec![json!({ "role": "system", "content": request.system })];
+
+        for message in &request.messages {
+            let mut text = Vec::new();
+            let mut images = Vec::new();
+
+            for part in &message.parts {
+                match part {
+                    Part::Text(t) => text.push(t.as_str()),
+                    Part::PngImage(data) => images.push(data.as_str()),
+                }
+            }
+
+            messages.push(json!({
+                "role": match message.role {
+                    Role::User => "user",
+                    Role::Assistant => "assistant",
+                },
+                "content": text.join("\n"),
+                "images": images,
+            }));
+        }
+
+        let mut body = json!({
+            "model": self.model,
+            "messages": messages,
+            "stream": stream,
+            "options": { "num_predict": request.max_output_tokens },
+        });
+
+        // ollama constrains generation to the schema passed as `format`
+        if let Some(schema) = &request.response_schema {
+            body["format"] = schema.schema.clone();
+        }
+
+        body
+    }
+}
+
+#[derive(Deserialize)]
+struct ChatResponse {
+    message: ChatMessage,
+    prompt_eval_count: Option<u32>,
+    eval_count: Option<u32>,
+}
+
+/// One line of a streamed reply, the last one has the token counts
+#[derive(Deserialize)]
+struct ChatChunk {
+    message: Option<ChatMessage>,
+    error: Option<String>,
+    prompt_eval_count: Option<u32>,
+    eval_count: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct ChatMessage {
+    #[serde(default)]
+    content: String,
+}
+
+#[async_trait]
+impl LlmProvider for OllamaProvider {
+    fn name(&self) -> &'static str {
+        "ollama"
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    async fn complete(&self, request: &LlmRequest) -> Result<LlmResponse, LlmError> {
+        let response = self
+            .http
+            .post(format!("{}/api/chat", self.base_url))
+            .json(&self.body(request, false))
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(LlmError::from_response(self.name(), response).await);
+        }
+
+        let body: ChatResponse = response
+            .json()
+            .await
+            .map_err(|e| LlmError::InvalidResponse(self.name(), e.to_string()))?;
+
+        // ollama leaves the prompt count out when the prompt was cached
+        let usage = body.eval_count.map(|output_tokens| TokenUsage {
+            input_tokens: body.prompt_eval_count.unwrap_or(0),
+            output_tokens,
+        });
+
+        Ok(LlmResponse {
+            text: body.message.content,
+            usage,
+        })
+    }
+
+    async fn complete_stream(
+        &self,
+        request: &LlmRequest,
+        on_text: &(dyn for<'t> Fn(&'t str) + Send + Sync),
+    ) -> Result<LlmResponse, LlmError> {
+        let response = self
+            .http
+            .post(format!("{}/api/chat", self.base_url))
+            .json(&self.body(request, true))
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(LlmError::from_response(self.name(), response).await);
+        }
+
+        let mut text = String::new();
+        let mut usage = None;
+        for_each_line(response, |line| {
+            if line.is_empty() {
+                return Ok(());
+            }
+            let chunk: ChatChunk = serde_json::from_str(line)
+                .map_err(|e| LlmError::InvalidResponse(self.name(), e.to_string()))?;
+            if let Some(error) = chunk.error {
+                return Err(LlmError::InvalidResponse(self.name(), error));
+            }
+
+            if let Some(message) = chunk.message.filter(|message| !message.content.is_empty()) {
+                on_text(&message.content);
+                text.push_str(&message.content);
+            }
+            if let Some(output_tokens) = chunk.eval_count {
+                usage = Some(TokenUsage {
+                    input_tokens: chunk.prompt_eval_count.unwrap_or(0),
+                    output_tokens,
+                });
+            }
+            Ok(())
+        })
+        .await?;
+
+        Ok(LlmResponse { text, usage })
+    }
+}
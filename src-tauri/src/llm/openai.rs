@@ -0,0 +1,393 @@
+use super::provider::{
+    LlmError, LlmProvider, LlmRequest, LlmResponse, Part, ResponseSchema, Role, TokenUsage,
+};
+use async_openai::{
+    config::OpenAIConfig,
+    types::{
+        responses::{
+            Content, ContentType, CreateResponse, CreateResponseArgs, Input, InputContent,
+            InputImageArgs, InputItem, InputMessageArgs, OutputContent, ResponseCompleted,
+            ResponseEvent, ResponseIncomplete, Role as ResponseRole, TextConfig,
+            TextResponseFormat,
+        },
+        ChatCompletionRequestAssistantMessageArgs, ChatCompletionRequestMessage,
+        ChatCompletionRequestMessageContentPartImageArgs,
+        ChatCompletionRequestMessageContentPartTextArgs, ChatCompletionRequestSystemMessageArgs,
+        ChatCompletionRequestUserMessageArgs, ChatCompletionRequestUserMessageContentPart,
+        ChatCompletionStreamOptions, CreateChatCompletionRequest, CreateChatCompletionRequestArgs,
+        ImageDetail, ImageUrlArgs, ResponseFormat, ResponseFormatJsonSchema,
+    },
+    Client,
+};
+use async_trait::async_trait;
+use backoff::ExponentialBackoff;
+use futures::StreamExt;
+use std::time::Duration;
+
+fn client(api_key: Option<&str>, base_url: Option<&str>) -> Client<OpenAIConfig> {
+    let mut config = OpenAIConfig::new().with_api_key(api_key.unwrap_or_default());
+    if let Some(base_url) = base_url {
+        config = config.with_api_base(base_url.trim_end_matches('/'));
+    }
+
+    // async-openai would otherwise retry rate limits for up to 15 minutes on its own,
+    // retries are handled by ResilientProvider so they share its timeout and circuit breaker
+    let no_retries = ExponentialBackoff {
+        max_elapsed_time: Some(Duration::ZERO),
+        ..Default::default()
+    };
+    Client::with_config(config).with_backoff(no_retries)
+}
+
+fn image_data_url(png_base64: &str) -> String {
+    format!("data:image/png;base64,{}", png_base64)
+}
+
+fn json_schema_format(schema: &ResponseSchema) -> ResponseFormatJsonSchema {
+    ResponseFormatJsonSchema {
+        description: None,
+        name: schema.name.clone(),
+        schema: Some(schema.schema.clone()),
+        strict: Some(true),
+    }
+}
+
+/// OpenAI itself, through the Responses API
+pub struct OpenAiProvider {
+    client: Client<OpenAIConfig>,
+    model: String,
+    has_api_key: bool,
+}
+
+impl OpenAiProvider {
+    pub fn new(model: String, api_key: Option<String>, base_url: Option<String>) -> Self {
+        // the key used to come from the environment only, keep honoring it
+        let api_key = api_key.or_else(|| {
+            std::env::var("OPENAI_API_KEY")
+                .ok()
+                .filter(|key| !key.trim().is_empty())
+        });
+
+        Self {
+            client: client(api_key.as_deref(), base_url.as_deref()),
+            model,
+            has_api_key: api_key.is_some(),
+        }
+    }
+
+    fn response_request(&self, request: &LlmRequest) -> Result<CreateResponse, LlmError> {
+        if !self.has_api_key {
+            return Err(LlmError::MissingApiKey(self.name()));
+        }
+
+        let mut items = vec![InputItem::Message(
+            InputMessageArgs::default()
+                .role(ResponseRole::System)
+                .content(request.system.clone())
+                .build()?,
+        )];
+
+        for message in &request.messages {
+            let role = match message.role {
+                Role::User => ResponseRole::User,
+                Role::Assistant => ResponseRole::Assistant,
+            };
+
+            for part in &message.parts {
+                let content = match part {
+                    Part::Text(text) => InputContent::TextInput(text.clone()),
+                    Part::PngImage(data) => {
+                        let im = InputImageArgs::default()
+                            .image_url(image_data_url(data))
+                            .detail(ImageDetail::Auto)
+                            .build()?;
+                        InputContent::InputItemContentList(vec![ContentType::InputImage(im)])
+                    }
+                };
+
+                items.push(InputItem::Message(
+                    InputMessageArgs::default()
+                        .role(role)
+                        .content(content)
+                        .build()?,
+                ));
+            }
+        }
+
+        let mut response_request = CreateResponseArgs::default();
+        response_request
+            .max_output_tokens(request.max_output_tokens)
+            .model(&self.model)
+            .input(Input::Items(items));
+
+        if let Some(schema) = &request.response_schema {
+            response_request.text(TextConfig {
+                format: TextResponseFormat::JsonSchema(json_schema_format(schema)),
+            });
+        }
+
+        Ok(response_request.build()?)
+    }
+}
+
+#[async_trait]
+impl LlmProvider for OpenAiProvider {
+    fn name(&self) -> &'static str {
+        "openai"
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    async fn complete(&self, request: &LlmRequest) -> Result<LlmResponse, LlmError> {
+        let response_request = self.response_request(request)?;
+
+        let response = self.client.responses().create(response_request).await?;
+
+        let text = response
+            .output
+            .iter()
+            .filter_map(extract_content_from_output)
+            .find(|text| !text.trim().is_empty())
+            .unwrap_or_default();
+
+        Ok(LlmResponse {
+            text,
+            usage: response.usage.map(|usage| TokenUsage {
+                input_tokens: usage.input_tokens,
+                output_tokens: usage.output_tokens,
+            }),
+        })
+    }
+
+    async fn complete_stream(
+        &self,
+        request: &LlmRequest,
+        on_text: &(dyn for<'t> Fn(&'t str) + Send + Sync),
+    ) -> Result<LlmResponse, LlmError> {
+        let response_request = self.response_request(request)?;
+
+        let mut stream = self
+            .client
+            .responses()
+            .create_stream(response_request)
+            .await?;
+
+        let mut text = String::new();
+        let mut usage = None;
+        while let Some(event) = stream.next().await {
+            match event? {
+                ResponseEvent::ResponseOutputTextDelta(delta) => {
+                    on_text(&delta.delta);
+                    text.push_str(&delta.delta);
+                }
+                ResponseEvent::ResponseCompleted(ResponseCompleted { response, .. })
+                | ResponseEvent::ResponseIncomplete(ResponseIncomplete { response, .. }) => {
+                    usage = response.usage.map(|usage| TokenUsage {
+                        input_tokens: usage.input_tokens,
+                        output_tokens: usage.output_tokens,
+                    });
+                }
+                ResponseEvent::ResponseFailed(failed) => {
+                    let message = failed
+                        .response
+                        .error
+                        .map(|error| error.message)
+                        .unwrap_or_else(|| "response failed".to_string());
+                    return Err(LlmError::InvalidResponse(self.name(), message));
+                }
+                ResponseEvent::ResponseError(error) => {
+                    return Err(LlmError::InvalidResponse(self.name(), error.message));
+                }
+                _ => {}
+            }
+        }
+
+        Ok(LlmResponse { text, usage })
+    }
+}
+
+fn extract_content_from_output(output: &OutputContent) -> Option<String> {
+    match output {
+        OutputContent::Message(message) => {
+            for content_item in &message.content {
+                match content_item {
+                    Content::OutputText(output_text) => {
+                        return Some(output_text.text.clone());
+                    }
+                    _ => continue,
+                }
+            }
+            None
+        }
+        _ => None,
+    }
+}
+
+/// Any server speaking the OpenAI Chat Completions API, e.g. vLLM or LM Studio
+pub struct OpenAiCompatibleProvider {
+    client: Client<OpenAIConfig>,
+    model: String,
+    has_base_url: bool,
+}
+
+impl OpenAiCompatibleProvider {
+    pub fn new(model: String, api_key: Option<String>, base_url: Option<String>) -> Self {
+        Self {
+            client: client(api_key.as_deref(), base_url.as_deref()),
+            model,
+            has_base_url: base_url.is_some(),
+        }
+    }
+
+    fn chat_request(&self, request: &LlmRequest) -> Result<CreateChatCompletionRequest, LlmError> {
+        if !self.has_base_url {
+            return Err(LlmError::MissingSetting(self.name(), "base URL"));
+        }
+        if self.model.is_empty() {
+            return Err(LlmError::MissingSetting(self.name(), "model"));
+        }
+
+        let mut messages: Vec<ChatCompletionRequestMessage> =
+            vec![ChatCompletionRequestSystemMessageArgs::default()
+                .content(request.system.clone())
+                .build()?
+                .into()];
+
+        for message in &request.messages {
+            match message.role {
+                Role::User => {
+                    let mut parts = Vec::new();
+                    for part in &message.parts {
+                        parts.push(match part {
+                            Part::Text(text) => ChatCompletionRequestUserMessageContentPart::Text(
+                                ChatCompletionRequestMessageContentPartTextArgs::default()
+                                    .text(text.clone())
+                                    .build()?,
+                            ),
+                            Part::PngImage(data) => {
+                                ChatCompletionRequestUserMessageContentPart::ImageUrl(
+                                    ChatCompletionRequestMessageContentPartImageArgs::default()
+                                        .image_url(
+                                            ImageUrlArgs::default()
+                                                .url(image_data_url(data))
+                                                .detail(ImageDetail::Auto)
+                                                .build()?,
+                                        )
+                                        .build()?,
+                                )
+                            }
+                        });
+                    }
+
+                    messages.push(
+                        ChatCompletionRequestUserMessageArgs::default()
+                            .content(parts)
+                            .build()?
+                            .into(),
+                    );
+                }
+                Role::Assistant => {
+                    let text: Vec<&str> = message
+                        .parts
+                        .iter()
+                        .filter_map(|part| match part {
+                            Part::Text(text) => Some(text.as_str()),
+                            Part::PngImage(_) => None,
+                        })
+                        .collect();
+
+                    messages.push(
+                        ChatCompletionRequestAssistantMessageArgs::default()
+                            .content(text.join("\n"))
+                            .build()?
+                            .into(),
+                    );
+                }
+            }
+        }
+
+        // most self-hosted servers still only understand `max_tokens`
+        #[allow(deprecated)]
+        let mut chat_request = CreateChatCompletionRequestArgs::default();
+        chat_request
+            .model(&self.model)
+            .max_tokens(request.max_output_tokens)
+            .messages(messages);
+
+        if let Some(schema) = &request.response_schema {
+            chat_request.response_format(ResponseFormat::JsonSchema {
+                json_schema: json_schema_format(schema),
+            });
+        }
+
+        Ok(chat_request.build()?)
+    }
+}
+
+#[async_trait]
+impl LlmProvider for OpenAiCompatibleProvider {
+    fn name(&self) -> &'static str {
+        "openai_compatible"
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    async fn complete(&self, request: &LlmRequest) -> Result<LlmResponse, LlmError> {
+        let chat_request = self.chat_request(request)?;
+
+        let response = self.client.chat().create(chat_request).await?;
+
+        let usage = response.usage.map(|usage| TokenUsage {
+            input_tokens: usage.prompt_tokens,
+            output_tokens: usage.completion_tokens,
+        });
+
+        let text = response
+            .choices
+            .into_iter()
+            .find_map(|choice| choice.message.content)
+            .unwrap_or_default();
+
+        Ok(LlmResponse { text, usage })
+    }
+
+    async fn complete_stream(
+        &self,
+        request: &LlmRequest,
+        on_text: &(dyn for<'t> Fn(&'t str) + Send + Sync),
+    ) -> Result<LlmResponse, LlmError> {
+        let mut chat_request = self.chat_request(request)?;
+        // token counts come in one last chunk after the text
+        chat_request.stream_options = Some(ChatCompletionStreamOptions {
+            include_usage: true,
+        });
+
+        let mut stream = self.client.chat().create_stream(chat_request).await?;
+
+        let mut text = String::new();
+        let mut usage = None;
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk?;
+            if let Some(delta) = chunk
+                .choices
+                .into_iter()
+                .find_map(|choice| choice.delta.content)
+            {
+                on_text(&delta);
+                text.push_str(&delta);
+            }
+            if let Some(chunk_usage) = chunk.usage {
+                usage = Some(TokenUsage {
+                    input_tokens: chunk_usage.prompt_tokens,
+                    output_tokens: chunk_usage.completion_tokens,
+                });
+            }
+        }
+
+        Ok(LlmResponse { text, usage })
+    }
+}
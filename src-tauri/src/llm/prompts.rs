@@ -0,0 +1,407 @@
+use super::cache;
+use super::schema::CATEGORIES;
+use crate::settings::{SettingsManager, SettingsManagerState};
+use crate::AppState;
+use regex::{Captures, Regex};
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::OnceLock;
+use tauri::State;
+
+pub const ANALYZE_SYSTEM: &str = "analyze_system";
+pub const ANALYZE_TEXT: &str = "analyze_text";
+pub const ANALYZE_IMAGE: &str = "analyze_image";
+pub const ANALYZE_PAGE: &str = "analyze_page";
+pub const SUMMARY: &str = "summary";
+pub const SUMMARIZE_CHUNK: &str = "summarize_chunk";
+pub const ASK_SYSTEM: &str = "ask_system";
+pub const ASK_QUESTION: &str = "ask_question";
+// followed by a category, e.g. `summary:error_log`
+const CATEGORY_SUMMARY_PREFIX: &str = "summary:";
+
+/// Every `{{variable}}` a template may use
+pub const VARIABLES: &[&str] = &[
+    "content",
+    "width",
+    "height",
+    "categories",
+    "language",
+    "summary_instruction",
+    "examples",
+    "part",
+    "parts",
+    "url",
+    "title",
+    "description",
+    "sources",
+    "question",
+];
+
+const DEFAULT_ANALYZE_SYSTEM: &str = r#"You are a clipboard content analyzer. Your job is to categorize content into a primary category, suggest relevant tags, give it a short title and, when asked, summarize it.
+
+IMPORTANT: Respond with ONLY a JSON object in this exact format:
+{
+  "category": "category_name",
+  "tags": ["tag1", "tag2", "tag3"],
+  "title": "Short descriptive title",
+  "summary": "- key point one\n- key point two"
+}
+
+Use these primary categories (choose the best fit):
+- code_snippet: Programming code, scripts, configuration files, JSON, XML, HTML, CSS, SQL queries
+- technical_advice: Technical explanations, troubleshooting steps, how-to guides, technical discussions
+- documentation: API docs, README files, technical specifications, user manuals
+- url: Web links, file paths, network addresses
+- credentials: Passwords, API keys, tokens, certificates (be careful with sensitive data)
+- data: CSV data, logs, structured data, database records
+- communication: Emails, messages, social media posts, chat conversations
+- notes: Personal notes, reminders, todo items, quick thoughts
+- reference: Phone numbers, addresses, contact info, reference materials
+- creative: Writing, stories, poems, creative content
+- business: Meeting notes, project plans, business documents, proposals
+- academic: Research, papers, citations, study materials
+- error_log: Error messages, stack traces, debug output
+- command: Terminal commands, CLI instructions, scripts to run
+- image: Screenshots, photos, diagrams, charts, memes, artwork, UI mockups
+- other: Content that doesn't fit the above categories
+
+For tags, suggest 2-4 specific, relevant tags that describe the content in more detail. Tags should be:
+- Lowercase
+- Single words or hyphenated (e.g., "react", "javascript", "error-handling", "screenshot", "diagram")
+- Specific to the technology, topic, context, or visual content
+
+For images, analyze the visual content and provide relevant tags like:
+- screenshot, diagram, chart, photo, artwork, meme, ui-design, wireframe
+- Technology-specific: react-app, code-editor, terminal, browser, mobile-app
+- Content-specific: dashboard, graph, error-message, documentation, social-media
+
+The title should be at most 8 words and describe what the content is, not repeat it. Write the title and summary in {{language}}.
+
+When a summary is requested, write a clear, concise bullet-point summary of the key points without citations or extra commentary. If the content came from a URL, give a short overview of the page's main points. When no summary is requested, set "summary" to an empty string.
+
+Examples:
+Input: "const handleClick = () => { console.log('clicked'); }"
+Output: {"category": "code_snippet", "tags": ["javascript", "function", "event-handler"], "title": "React click handler", "summary": ""}
+
+Input: "https://github.com/user/repo"
+Output: {"category": "url", "tags": ["github", "repository", "git"], "title": "GitHub repository user/repo", "summary": "- GitHub repository page for user/repo"}
+
+Input: [Image of a code editor with React code]
+Output: {"category": "image", "tags": ["screenshot", "code-editor", "react", "development"], "title": "React code in an editor", "summary": "- Screenshot of a code editor showing a React component"}
+
+Input: [Image of a terminal with error messages]
+Output: {"category": "image", "tags": ["screenshot", "terminal", "error-message", "debugging"], "title": "Terminal error output", "summary": "- Terminal showing a failed command and its error messages"}
+
+Input: [Image of a website mockup]
+Output: {"category": "image", "tags": ["screenshot", "ui-design", "website", "mockup"], "title": "Website landing page mockup", "summary": "- Mockup of a website landing page layout"}"#;
+
+const DEFAULTS: &[(&str, &str)] = &[
+    (ANALYZE_SYSTEM, DEFAULT_ANALYZE_SYSTEM),
+    (
+        ANALYZE_TEXT,
+        "Analyze this text content. {{summary_instruction}}{{examples}}\n\n{{content}}",
+    ),
+    (
+        ANALYZE_IMAGE,
+        "Analyze this image content. Image dimensions: {{width}}x{{height}}. Analyze what you see in the image and provide appropriate category, tags and title. {{summary_instruction}}",
+    ),
+    (
+        ANALYZE_PAGE,
+        "Analyze this web page, fetched from {{url}}. Categorize the link itself as \"url\" and tag it by what the page is about. {{summary_instruction}}{{examples}}\n\nTitle: {{title}}\nDescription: {{description}}\n\n{{content}}",
+    ),
+    (SUMMARY, "Include a summary."),
+    (
+        SUMMARIZE_CHUNK,
+        "This is part {{part}} of {{parts}} of a longer text. Summarize it in a few concise bullet points in {{language}}, keeping names, numbers, errors and decisions. Reply with only the bullet points.\n\n{{content}}",
+    ),
+    (
+        ASK_SYSTEM,
+        "You answer questions about the user's saved clipboard clips, using only the clips given as sources and never outside knowledge. After every statement drawn from a clip, cite it as [clip:<id>] with the id shown for that clip, e.g. [clip:42], and cite several as [clip:3][clip:7]. Never cite a clip that isn't listed. If the sources don't answer the question, say so plainly instead of guessing. Quote commands, code and values exactly as they appear in the clip. Answer concisely in {{language}}.",
+    ),
+    (
+        ASK_QUESTION,
+        "Sources from the user's clips:\n\n{{sources}}\n\nQuestion: {{question}}",
+    ),
+    (
+        "summary:error_log",
+        "Include a summary with one bullet each for what failed, its probable cause and the most likely fix.",
+    ),
+    (
+        "summary:code_snippet",
+        "Include a summary of what the code does, which language it is in and anything notable like dependencies or side effects.",
+    ),
+    (
+        "summary:communication",
+        "Include a summary of the message's main points, followed by any requests or action items.",
+    ),
+    (
+        "summary:data",
+        "Include a summary of what the data describes, its columns or fields and anything that stands out.",
+    ),
+];
+
+fn default_body(name: &str) -> Option<&'static str> {
+    DEFAULTS
+        .iter()
+        .find(|(default, _)| *default == name)
+        .map(|(_, body)| *body)
+}
+
+fn is_known_name(name: &str) -> bool {
+    default_body(name).is_some()
+        || name
+            .strip_prefix(CATEGORY_SUMMARY_PREFIX)
+            .is_some_and(|category| CATEGORIES.contains(&category))
+}
+
+fn variable_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"\{\{\s*(\w+)\s*\}\}").expect("invalid variable pattern"))
+}
+
+/// The prompt templates in effect, user edits layered over the built-in defaults
+#[derive(Debug, Clone)]
+pub struct PromptTemplates {
+    custom: HashMap<String, String>,
+    language: String,
+}
+
+impl PromptTemplates {
+    pub fn load(db_path: &Path, settings: &SettingsManager) -> Self {
+        let custom = Connection::open(db_path)
+            .and_then(|conn| active_templates(&conn))
+            .unwrap_or_else(|e| {
+                eprintln!("Failed to load prompt templates, using defaults: {}", e);
+                HashMap::new()
+            });
+
+        let language = settings
+            .get_setting("llm_language")
+            .filter(|language| !language.trim().is_empty())
+            .unwrap_or_else(|| "English".to_string());
+
+        Self { custom, language }
+    }
+
+    /// The active body of a template, empty for a category without a summary template
+    pub fn get(&self, name: &str) -> &str {
+        self.custom
+            .get(name)
+            .map(String::as_str)
+            .or_else(|| default_body(name))
+            .unwrap_or_default()
+    }
+
+    /// How to ask for a summary of content that looks like `category`
+    pub fn summary_instruction(&self, category: &str) -> &str {
+        match self.get(&format!("{CATEGORY_SUMMARY_PREFIX}{category}")) {
+            "" => self.get(SUMMARY),
+            instruction => instruction,
+        }
+    }
+
+    /// Fill in a template. Values are inserted in one pass, so `{{...}}` inside
+    /// clipped content is left alone
+    pub fn render(&self, name: &str, values: &[(&str, &str)]) -> String {
+        let categories = CATEGORIES.join(", ");
+
+        variable_pattern()
+            .replace_all(self.get(name), |caps: &Captures| {
+                let variable = &caps[1];
+                match variable {
+                    "language" => self.language.clone(),
+                    "categories" => categories.clone(),
+                    _ => values
+                        .iter()
+                        .find(|(name, _)| *name == variable)
+                        .map(|(_, value)| value.to_string())
+                        .unwrap_or_default(),
+                }
+            })
+            .into_owned()
+    }
+
+    /// Fingerprint of the templates a request renders plus anything else that shapes it, for
+    /// the LLM cache. Edits to other templates leave it alone
+    pub fn version(&self, templates: &[&str], extra: &[&str]) -> String {
+        let mut parts: Vec<&str> = templates.iter().map(|name| self.get(name)).collect();
+        parts.push(&self.language);
+        parts.extend_from_slice(extra);
+
+        cache::prompt_version(&parts)
+    }
+}
+
+// latest version of each edited template, unless that version was a reset
+fn active_templates(conn: &Connection) -> rusqlite::Result<HashMap<String, String>> {
+    let mut stmt = conn.prepare(
+        r#"
+        SELECT name, body FROM prompt_templates t
+        WHERE version = (SELECT MAX(version) FROM prompt_templates WHERE name = t.name)
+          AND body IS NOT NULL
+        "#,
+    )?;
+    let templates = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<HashMap<_, _>, _>>()?;
+    Ok(templates)
+}
+
+#[derive(Debug, Serialize)]
+pub struct PromptTemplateInfo {
+    pub name: String,
+    pub body: String,
+    /// 0 for a template that was never edited
+    pub version: i64,
+    pub is_default: bool,
+    pub default_body: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PromptTemplateVersion {
+    pub version: i64,
+    /// `None` for a reset to the default
+    pub body: Option<String>,
+    pub created_at: String,
+}
+
+/// Every template with its active body, including per-category summaries that were edited
+#[tauri::command]
+pub fn get_prompt_templates(
+    state: State<'_, AppState>,
+    settings: State<'_, SettingsManagerState>,
+) -> Result<Vec<PromptTemplateInfo>, String> {
+    let conn =
+        Connection::open(&state.db_path).map_err(|e| format!("Failed to open database: {e}"))?;
+
+    let mut stmt = conn
+        .prepare("SELECT name, MAX(version) FROM prompt_templates GROUP BY name")
+        .map_err(|e| format!("Failed to prepare statement: {e}"))?;
+    let versions = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+        })
+        .map_err(|e| format!("Failed to execute query: {e}"))?
+        .collect::<Result<HashMap<_, _>, _>>()
+        .map_err(|e| format!("Failed to process row: {e}"))?;
+
+    let templates = PromptTemplates::load(&state.db_path, &settings.0);
+
+    let mut names: Vec<String> = DEFAULTS.iter().map(|(name, _)| name.to_string()).collect();
+    for name in versions.keys() {
+        if !names.contains(name) && is_known_name(name) {
+            names.push(name.clone());
+        }
+    }
+
+    Ok(names
+        .into_iter()
+        .map(|name| PromptTemplateInfo {
+            body: templates.get(&name).to_string(),
+            version: versions.get(&name).copied().unwrap_or(0),
+            is_default: !templates.custom.contains_key(&name),
+            default_body: default_body(&name).map(str::to_string),
+            name,
+        })
+        .collect())
+}
+
+/// Every saved version of a template, newest first
+#[tauri::command]
+pub fn get_prompt_template_history(
+    state: State<'_, AppState>,
+    name: String,
+) -> Result<Vec<PromptTemplateVersion>, String> {
+    let conn =
+        Connection::open(&state.db_path).map_err(|e| format!("Failed to open database: {e}"))?;
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT version, body, created_at FROM prompt_templates WHERE name = ? ORDER BY version DESC",
+        )
+        .map_err(|e| format!("Failed to prepare statement: {e}"))?;
+
+    let history = stmt
+        .query_map(params![name], |row| {
+            Ok(PromptTemplateVersion {
+                version: row.get(0)?,
+                body: row.get(1)?,
+                created_at: row.get(2)?,
+            })
+        })
+        .map_err(|e| format!("Failed to execute query: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to process row: {e}"))?;
+
+    Ok(history)
+}
+
+/// Save a new version of a template, returns its version number
+#[tauri::command]
+pub fn set_prompt_template(
+    state: State<'_, AppState>,
+    name: String,
+    body: String,
+) -> Result<i64, String> {
+    if !is_known_name(&name) {
+        return Err(format!(
+            "Unknown prompt template \"{name}\", expected one of {} or summary:<category>",
+            DEFAULTS
+                .iter()
+                .map(|(name, _)| *name)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+    if body.trim().is_empty() {
+        return Err("Prompt template can't be empty".to_string());
+    }
+
+    for caps in variable_pattern().captures_iter(&body) {
+        if !VARIABLES.contains(&&caps[1]) {
+            return Err(format!(
+                "Unknown variable {{{{{}}}}}, available: {}",
+                &caps[1],
+                VARIABLES.join(", ")
+            ));
+        }
+    }
+    // without the content there is nothing for the model to analyze
+    if (name == ANALYZE_TEXT || name == ANALYZE_PAGE) && !body.contains("{{content}}") {
+        return Err(format!("The {name} template must include {{{{content}}}}"));
+    }
+    if name == ASK_QUESTION && !(body.contains("{{sources}}") && body.contains("{{question}}")) {
+        return Err(format!(
+            "The {name} template must include {{{{sources}}}} and {{{{question}}}}"
+        ));
+    }
+
+    save_version(&state.db_path, &name, Some(&body))
+}
+
+/// Go back to the built-in template. Earlier versions stay in the history
+#[tauri::command]
+pub fn reset_prompt_template(state: State<'_, AppState>, name: String) -> Result<i64, String> {
+    if !is_known_name(&name) {
+        return Err(format!("Unknown prompt template \"{name}\""));
+    }
+
+    save_version(&state.db_path, &name, None)
+}
+
+fn save_version(db_path: &Path, name: &str, body: Option<&str>) -> Result<i64, String> {
+    let conn = Connection::open(db_path).map_err(|e| format!("Failed to open database: {e}"))?;
+
+    conn.query_row(
+        r#"
+        INSERT INTO prompt_templates (name, version, body)
+        VALUES (?1, (SELECT COALESCE(MAX(version), 0) + 1 FROM prompt_templates WHERE name = ?1), ?2)
+        RETURNING version
+        "#,
+        params![name, body],
+        |row| row.get(0),
+    )
+    .map_err(|e| format!("Failed to save prompt template: {e}"))
+}
@@ -0,0 +1,318 @@
+use super::anthropic::AnthropicProvider;
+use super::ollama::OllamaProvider;
+use super::openai::{OpenAiCompatibleProvider, OpenAiProvider};
+use super::resilience::{ResilientProvider, RetryPolicy};
+use crate::settings::SettingsManager;
+use async_openai::error::OpenAIError;
+use async_trait::async_trait;
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum LlmError {
+    #[error("No API key configured for {0}")]
+    MissingApiKey(&'static str),
+    #[error("No {1} configured for {0}")]
+    MissingSetting(&'static str, &'static str),
+    #[error("OpenAI error: {0}")]
+    OpenAi(#[from] OpenAIError),
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("{provider} returned HTTP {status}: {message}")]
+    Status {
+        provider: &'static str,
+        status: u16,
+        message: String,
+        retry_after: Option<Duration>,
+    },
+    #[error("Unexpected response from {0}: {1}")]
+    InvalidResponse(&'static str, String),
+    #[error("Request timed out after {0}s")]
+    Timeout(u64),
+    #[error("AI is paused after repeated failures, retrying in {0}s")]
+    CircuitOpen(u64),
+    #[error("Request was cancelled")]
+    Cancelled,
+    #[error("Monthly LLM budget of ${0:.2} reached, using offline categorization")]
+    BudgetExceeded(f64),
+}
+
+impl LlmError {
+    /// Build a `Status` error from a failed response, keeping its Retry-After hint
+    pub async fn from_response(provider: &'static str, response: reqwest::Response) -> Self {
+        // only the delta-seconds form, providers don't send HTTP dates here
+        let retry_after = response
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse::<u64>().ok())
+            .map(Duration::from_secs);
+
+        LlmError::Status {
+            provider,
+            status: response.status().as_u16(),
+            message: response.text().await.unwrap_or_default(),
+            retry_after,
+        }
+    }
+
+    /// Whether trying the same request again could succeed
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            LlmError::Timeout(_) => true,
+            LlmError::Http(e) | LlmError::OpenAi(OpenAIError::Reqwest(e)) => {
+                e.is_timeout() || e.is_connect() || e.is_request()
+            }
+            LlmError::Status { status, .. } => matches!(status, 408 | 429 | 500..=599),
+            // async-openai hides the status code: rate limits keep their error code and
+            // server errors come back as a bare message with neither type nor code
+            LlmError::OpenAi(OpenAIError::ApiError(e)) => {
+                e.code.as_deref() == Some("rate_limit_exceeded")
+                    || (e.r#type.is_none() && e.code.is_none())
+            }
+            _ => false,
+        }
+    }
+
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            LlmError::Status { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+
+    /// Short code stored with the fallback recorded when a request fails
+    pub fn reason(&self) -> &'static str {
+        match self {
+            LlmError::Timeout(_) => "timeout",
+            LlmError::CircuitOpen(_) => "circuit_open",
+            LlmError::Cancelled => "cancelled",
+            LlmError::BudgetExceeded(_) => "budget_exceeded",
+            _ => "request_failed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    User,
+    Assistant,
+}
+
+#[derive(Debug, Clone)]
+pub enum Part {
+    Text(String),
+    /// base64 encoded PNG, the format every clipboard image is stored in
+    PngImage(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Message {
+    pub role: Role,
+    pub parts: Vec<Part>,
+}
+
+/// JSON schema the reply has to follow, for providers that can enforce one
+#[derive(Debug, Clone)]
+pub struct ResponseSchema {
+    pub name: String,
+    pub schema: serde_json::Value,
+}
+
+/// A provider-agnostic chat request: one system prompt and the conversation after it
+#[derive(Debug, Clone)]
+pub struct LlmRequest {
+    pub system: String,
+    pub messages: Vec<Message>,
+    pub max_output_tokens: u32,
+    pub response_schema: Option<ResponseSchema>,
+}
+
+impl LlmRequest {
+    pub fn new(system: impl Into<String>, max_output_tokens: u32) -> Self {
+        Self {
+            system: system.into(),
+            messages: Vec::new(),
+            max_output_tokens,
+            response_schema: None,
+        }
+    }
+
+    pub fn json_schema(mut self, name: impl Into<String>, schema: serde_json::Value) -> Self {
+        self.response_schema = Some(ResponseSchema {
+            name: name.into(),
+            schema,
+        });
+        self
+    }
+
+    pub fn user_text(mut self, text: impl Into<String>) -> Self {
+        self.messages.push(Message {
+            role: Role::User,
+            parts: vec![Part::Text(text.into())],
+        });
+        self
+    }
+
+    pub fn user_image(mut self, png_base64: impl Into<String>) -> Self {
+        self.messages.push(Message {
+            role: Role::User,
+            parts: vec![Part::PngImage(png_base64.into())],
+        });
+        self
+    }
+
+    pub fn assistant_text(mut self, text: impl Into<String>) -> Self {
+        self.messages.push(Message {
+            role: Role::Assistant,
+            parts: vec![Part::Text(text.into())],
+        });
+        self
+    }
+}
+
+/// Tokens billed for one call, as reported by the provider
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TokenUsage {
+    pub input_tokens: u32,
+    pub output_tokens: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct LlmResponse {
+    pub text: String,
+    /// `None` when the provider didn't say
+    pub usage: Option<TokenUsage>,
+}
+
+/// Read a streamed response body line by line as it arrives, for newline delimited JSON and
+/// server-sent events. A last line without a newline is passed on too
+pub(super) async fn for_each_line(
+    mut response: reqwest::Response,
+    mut on_line: impl FnMut(&str) -> Result<(), LlmError>,
+) -> Result<(), LlmError> {
+    // bytes, so a character split across chunks isn't cut in half
+    let mut pending: Vec<u8> = Vec::new();
+
+    while let Some(chunk) = response.chunk().await? {
+        pending.extend_from_slice(&chunk);
+        while let Some(end) = pending.iter().position(|byte| *byte == b'\n') {
+            let line: Vec<u8> = pending.drain(..=end).collect();
+            on_line(String::from_utf8_lossy(&line).trim_end())?;
+        }
+    }
+
+    if !pending.is_empty() {
+        on_line(String::from_utf8_lossy(&pending).trim_end())?;
+    }
+    Ok(())
+}
+
+/// A chat model backend. Implementations translate an [`LlmRequest`] into their own API
+#[async_trait]
+pub trait LlmProvider: Send + Sync {
+    /// Short provider id used in logs and settings, e.g. `openai`
+    fn name(&self) -> &'static str;
+
+    fn model(&self) -> &str;
+
+    async fn complete(&self, request: &LlmRequest) -> Result<LlmResponse, LlmError>;
+
+    /// Like `complete`, handing the reply to `on_text` piece by piece as it is generated.
+    /// Providers that can't stream hand it over in one piece at the end
+    async fn complete_stream(
+        &self,
+        request: &LlmRequest,
+        on_text: &(dyn for<'t> Fn(&'t str) + Send + Sync),
+    ) -> Result<LlmResponse, LlmError> {
+        let response = self.complete(request).await?;
+        on_text(&response.text);
+        Ok(response)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProviderKind {
+    OpenAi,
+    OpenAiCompatible,
+    Anthropic,
+    Ollama,
+}
+
+impl ProviderKind {
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "openai_compatible" => ProviderKind::OpenAiCompatible,
+            "anthropic" => ProviderKind::Anthropic,
+            "ollama" => ProviderKind::Ollama,
+            _ => ProviderKind::OpenAi,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProviderKind::OpenAi => "openai",
+            ProviderKind::OpenAiCompatible => "openai_compatible",
+            ProviderKind::Anthropic => "anthropic",
+            ProviderKind::Ollama => "ollama",
+        }
+    }
+
+    // an OpenAI-compatible server can host anything, so it has no sensible default
+    fn default_model(&self) -> &'static str {
+        match self {
+            ProviderKind::OpenAi => "gpt-4o",
+            ProviderKind::OpenAiCompatible => "",
+            ProviderKind::Anthropic => "claude-sonnet-4-5",
+            ProviderKind::Ollama => "llama3.2",
+        }
+    }
+}
+
+/// Which backend, model and endpoint to use, read from the `llm_*` settings
+#[derive(Debug, Clone)]
+pub struct LlmConfig {
+    pub provider: ProviderKind,
+    pub model: String,
+    pub base_url: Option<String>,
+    pub api_key: Option<String>,
+    pub retry: RetryPolicy,
+}
+
+impl LlmConfig {
+    pub fn from_settings(settings: &SettingsManager) -> Self {
+        let non_empty = |key: &str| {
+            settings
+                .get_setting(key)
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+
+        let provider = ProviderKind::parse(&non_empty("llm_provider").unwrap_or_default());
+
+        Self {
+            provider,
+            model: non_empty("llm_model").unwrap_or_else(|| provider.default_model().to_string()),
+            base_url: non_empty("llm_base_url"),
+            api_key: non_empty("llm_api_key"),
+            retry: RetryPolicy::from_settings(settings),
+        }
+    }
+}
+
+pub fn build_provider(config: &LlmConfig) -> Box<dyn LlmProvider> {
+    let model = config.model.clone();
+    let base_url = config.base_url.clone();
+    let api_key = config.api_key.clone();
+
+    let provider: Box<dyn LlmProvider> = match config.provider {
+        ProviderKind::OpenAi => Box::new(OpenAiProvider::new(model, api_key, base_url)),
+        ProviderKind::OpenAiCompatible => {
+            Box::new(OpenAiCompatibleProvider::new(model, api_key, base_url))
+        }
+        ProviderKind::Anthropic => Box::new(AnthropicProvider::new(model, api_key, base_url)),
+        ProviderKind::Ollama => Box::new(OllamaProvider::new(model, base_url)),
+    };
+
+    Box::new(ResilientProvider::new(provider, config.retry.clone()))
+}
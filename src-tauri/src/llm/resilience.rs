@@ -0,0 +1,404 @@
+use super::provider::{LlmError, LlmProvider, LlmRequest, LlmResponse};
+use crate::settings::SettingsManager;
+use async_trait::async_trait;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+const BACKOFF_BASE: Duration = Duration::from_millis(500);
+const BACKOFF_MAX: Duration = Duration::from_secs(20);
+// a Retry-After longer than this isn't worth holding a capture open for
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// Timeouts, retries and circuit breaker thresholds, read from the `llm_*` settings
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// per attempt, for a stream until its first text and then between chunks
+    pub timeout: Duration,
+    pub max_retries: u32,
+    pub circuit_threshold: u32,
+    pub circuit_cooldown: Duration,
+}
+
+impl RetryPolicy {
+    pub fn from_settings(settings: &SettingsManager) -> Self {
+        let number = |key: &str, default: u64| {
+            settings
+                .get_setting(key)
+                .and_then(|value| value.trim().parse::<u64>().ok())
+                .unwrap_or(default)
+        };
+
+        Self {
+            timeout: Duration::from_secs(number("llm_timeout_secs", 30).max(1)),
+            max_retries: number("llm_max_retries", 3) as u32,
+            circuit_threshold: number("llm_circuit_threshold", 5).max(1) as u32,
+            circuit_cooldown: Duration::from_secs(number("llm_circuit_cooldown_secs", 300)),
+        }
+    }
+}
+
+/// Wraps a provider with a per-attempt timeout, retries on transient errors and the circuit breaker
+pub struct ResilientProvider {
+    inner: Box<dyn LlmProvider>,
+    policy: RetryPolicy,
+}
+
+impl ResilientProvider {
+    pub fn new(inner: Box<dyn LlmProvider>, policy: RetryPolicy) -> Self {
+        Self { inner, policy }
+    }
+
+    /// Make `call` with a per-attempt timeout, retrying transient failures as long as
+    /// nothing has streamed yet. Text already shown can't be taken back. `last_output` is
+    /// when text last streamed, once there is some the timeout counts from there, so a long
+    /// answer only times out when it stalls
+    async fn run<F, Fut>(
+        &self,
+        call: F,
+        last_output: impl Fn() -> Option<Instant>,
+    ) -> Result<LlmResponse, LlmError>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<LlmResponse, LlmError>>,
+    {
+        let name = self.inner.name();
+        let _probe = match breaker_check(name) {
+            Ok(probing) => probing.then_some(ProbeGuard(name)),
+            Err(remaining) => return Err(LlmError::CircuitOpen(remaining.as_secs())),
+        };
+
+        let mut attempt = 0;
+        loop {
+            let result = self.with_timeout(call(), &last_output).await;
+            let error = match result {
+                Ok(response) => {
+                    breaker_success(name);
+                    return Ok(response);
+                }
+                Err(e) => e,
+            };
+
+            if !error.is_retryable() {
+                // the provider answered, it just didn't like the request
+                breaker_success(name);
+                return Err(error);
+            }
+
+            let delay = match error.retry_after() {
+                Some(retry_after) if retry_after > MAX_RETRY_AFTER => None,
+                Some(retry_after) => Some(retry_after),
+                None => Some(backoff_delay(attempt)),
+            };
+
+            let streamed = last_output().is_some();
+            if streamed && matches!(error, LlmError::Timeout(_)) {
+                // the provider was answering, a stalled stream says little about its health
+                return Err(error);
+            }
+
+            match delay {
+                Some(delay) if attempt < self.policy.max_retries && !streamed => {
+                    attempt += 1;
+                    eprintln!(
+                        "{} request failed ({}), retry {}/{} in {:?}",
+                        name, error, attempt, self.policy.max_retries, delay
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                _ => {
+                    breaker_failure(name, &self.policy);
+                    return Err(error);
+                }
+            }
+        }
+    }
+
+    // the timeout runs from the start of the call until the first output, then from each
+    // output to the next
+    async fn with_timeout(
+        &self,
+        call: impl Future<Output = Result<LlmResponse, LlmError>>,
+        last_output: &impl Fn() -> Option<Instant>,
+    ) -> Result<LlmResponse, LlmError> {
+        let started = Instant::now();
+        tokio::pin!(call);
+
+        loop {
+            let since = last_output().unwrap_or(started);
+            tokio::select! {
+                result = &mut call => return result,
+                _ = tokio::time::sleep_until((since + self.policy.timeout).into()) => {
+                    if last_output().unwrap_or(started) == since {
+                        return Err(LlmError::Timeout(self.policy.timeout.as_secs()));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl LlmProvider for ResilientProvider {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    fn model(&self) -> &str {
+        self.inner.model()
+    }
+
+    async fn complete(&self, request: &LlmRequest) -> Result<LlmResponse, LlmError> {
+        self.run(|| self.inner.complete(request), || None).await
+    }
+
+    async fn complete_stream(
+        &self,
+        request: &LlmRequest,
+        on_text: &(dyn for<'t> Fn(&'t str) + Send + Sync),
+    ) -> Result<LlmResponse, LlmError> {
+        let last_output = Mutex::new(None);
+        let forward = |text: &str| {
+            *last_output.lock().unwrap() = Some(Instant::now());
+            on_text(text);
+        };
+
+        self.run(
+            || self.inner.complete_stream(request, &forward),
+            || *last_output.lock().unwrap(),
+        )
+        .await
+    }
+}
+
+// exponential backoff with "equal jitter": at least half the step, plus a random share of the rest
+fn backoff_delay(attempt: u32) -> Duration {
+    let step = BACKOFF_BASE
+        .saturating_mul(2u32.saturating_pow(attempt))
+        .min(BACKOFF_MAX);
+    let half = step / 2;
+    half + Duration::from_millis(rand::random_range(0..=half.as_millis() as u64))
+}
+
+#[derive(Debug, Default)]
+struct Breaker {
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+    // after the cooldown a single probe request is let through
+    probing: bool,
+}
+
+// one breaker per provider, shared by every client the app builds
+fn breakers() -> &'static Mutex<HashMap<&'static str, Breaker>> {
+    static BREAKERS: OnceLock<Mutex<HashMap<&'static str, Breaker>>> = OnceLock::new();
+    BREAKERS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// `Ok(true)` when this call is the probe after a cooldown, `Err` with the time left while open
+fn breaker_check(provider: &'static str) -> Result<bool, Duration> {
+    let mut breakers = breakers().lock().unwrap();
+    let breaker = breakers.entry(provider).or_default();
+
+    let Some(open_until) = breaker.open_until else {
+        return Ok(false);
+    };
+    let now = Instant::now();
+    if now < open_until {
+        return Err(open_until - now);
+    }
+    if breaker.probing {
+        // another request is already probing, keep everyone else out until it settles
+        return Err(Duration::ZERO);
+    }
+
+    breaker.probing = true;
+    Ok(true)
+}
+
+// releases the probe slot even when the probing request is cancelled midway
+struct ProbeGuard(&'static str);
+
+impl Drop for ProbeGuard {
+    fn drop(&mut self) {
+        if let Some(breaker) = breakers().lock().unwrap().get_mut(self.0) {
+            breaker.probing = false;
+        }
+    }
+}
+
+fn breaker_success(provider: &'static str) {
+    let mut breakers = breakers().lock().unwrap();
+    if let Some(breaker) = breakers.get_mut(provider) {
+        if breaker.open_until.is_some() {
+            println!("{} is responding again, resuming AI", provider);
+        }
+        *breaker = Breaker::default();
+    }
+}
+
+fn breaker_failure(provider: &'static str, policy: &RetryPolicy) {
+    let mut breakers = breakers().lock().unwrap();
+    let breaker = breakers.entry(provider).or_default();
+
+    breaker.consecutive_failures += 1;
+    if breaker.probing || breaker.consecutive_failures >= policy.circuit_threshold {
+        eprintln!(
+            "{} failed {} times in a row, pausing AI for {:?}",
+            provider, breaker.consecutive_failures, policy.circuit_cooldown
+        );
+        breaker.open_until = Some(Instant::now() + policy.circuit_cooldown);
+        breaker.probing = false;
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct LlmHealth {
+    pub provider: String,
+    /// closed | open | half_open
+    pub state: &'static str,
+    pub consecutive_failures: u32,
+    pub retry_in_secs: u64,
+}
+
+/// Circuit breaker state of every provider that has been called since startup
+#[tauri::command]
+pub fn get_llm_health() -> Vec<LlmHealth> {
+    let breakers = breakers().lock().unwrap();
+    let now = Instant::now();
+
+    breakers
+        .iter()
+        .map(|(provider, breaker)| {
+            let (state, retry_in) = match breaker.open_until {
+                None => ("closed", Duration::ZERO),
+                Some(until) if until > now => ("open", until - now),
+                Some(_) => ("half_open", Duration::ZERO),
+            };
+
+            LlmHealth {
+                provider: provider.to_string(),
+                state,
+                consecutive_failures: breaker.consecutive_failures,
+                retry_in_secs: retry_in.as_secs(),
+            }
+        })
+        .collect()
+}
+
+/// Close every circuit so AI resumes immediately instead of waiting out the cooldown
+#[tauri::command]
+pub fn reset_llm_circuit() {
+    breakers().lock().unwrap().clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // streams `chunks` pieces `gap` apart, then goes quiet for `stall` before finishing
+    struct SlowProvider {
+        name: &'static str,
+        chunks: usize,
+        gap: Duration,
+        stall: Duration,
+    }
+
+    #[async_trait]
+    impl LlmProvider for SlowProvider {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        fn model(&self) -> &str {
+            "test"
+        }
+
+        async fn complete(&self, _request: &LlmRequest) -> Result<LlmResponse, LlmError> {
+            tokio::time::sleep(self.gap * self.chunks as u32 + self.stall).await;
+            Ok(LlmResponse {
+                text: "x".repeat(self.chunks),
+                usage: None,
+            })
+        }
+
+        async fn complete_stream(
+            &self,
+            _request: &LlmRequest,
+            on_text: &(dyn for<'t> Fn(&'t str) + Send + Sync),
+        ) -> Result<LlmResponse, LlmError> {
+            for _ in 0..self.chunks {
+                tokio::time::sleep(self.gap).await;
+                on_text("x");
+            }
+            tokio::time::sleep(self.stall).await;
+            Ok(LlmResponse {
+                text: "x".repeat(self.chunks),
+                usage: None,
+            })
+        }
+    }
+
+    fn provider(
+        name: &'static str,
+        chunks: usize,
+        gap_ms: u64,
+        stall_ms: u64,
+    ) -> ResilientProvider {
+        ResilientProvider::new(
+            Box::new(SlowProvider {
+                name,
+                chunks,
+                gap: Duration::from_millis(gap_ms),
+                stall: Duration::from_millis(stall_ms),
+            }),
+            RetryPolicy {
+                timeout: Duration::from_millis(100),
+                max_retries: 0,
+                circuit_threshold: 5,
+                circuit_cooldown: Duration::from_secs(60),
+            },
+        )
+    }
+
+    fn failures(name: &'static str) -> u32 {
+        breakers()
+            .lock()
+            .unwrap()
+            .get(name)
+            .map_or(0, |breaker| breaker.consecutive_failures)
+    }
+
+    #[tokio::test]
+    async fn stream_longer_than_the_timeout_completes_while_text_keeps_coming() {
+        let provider = provider("steady", 8, 40, 0);
+        let request = LlmRequest::new("system", 10);
+
+        let response = provider.complete_stream(&request, &|_| {}).await.unwrap();
+
+        assert_eq!(response.text, "xxxxxxxx");
+    }
+
+    #[tokio::test]
+    async fn stalled_stream_times_out_without_tripping_the_breaker() {
+        let provider = provider("stalled", 2, 10, 1000);
+        let request = LlmRequest::new("system", 10);
+
+        let result = provider.complete_stream(&request, &|_| {}).await;
+
+        assert!(matches!(result, Err(LlmError::Timeout(_))));
+        assert_eq!(failures("stalled"), 0);
+    }
+
+    #[tokio::test]
+    async fn silent_call_times_out_and_counts_as_a_failure() {
+        let provider = provider("silent", 0, 0, 1000);
+        let request = LlmRequest::new("system", 10);
+
+        let result = provider.complete_stream(&request, &|_| {}).await;
+
+        assert!(matches!(result, Err(LlmError::Timeout(_))));
+        assert_eq!(failures("silent"), 1);
+    }
+}
@@ -0,0 +1,484 @@
+use serde_json::{json, Value};
+
+/// Every category the analysis prompt allows
+pub const CATEGORIES: &[&str] = &[
+    "code_snippet",
+    "technical_advice",
+    "documentation",
+    "url",
+    "credentials",
+    "data",
+    "communication",
+    "notes",
+    "reference",
+    "creative",
+    "business",
+    "academic",
+    "error_log",
+    "command",
+    "image",
+    "other",
+];
+
+const MAX_TAGS: usize = 4;
+// a summary is a handful of bullet points, anything longer is the model rambling or repeating
+const MAX_SUMMARY_CHARS: usize = 2000;
+
+/// JSON schema for the combined analysis reply
+pub fn analysis_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "category": { "type": "string", "enum": CATEGORIES },
+            "tags": { "type": "array", "items": { "type": "string" } },
+            "title": { "type": "string" },
+            "summary": { "type": "string" }
+        },
+        "required": ["category", "tags", "title", "summary"],
+        "additionalProperties": false
+    })
+}
+
+/// Why a field of the reply couldn't be used
+#[derive(Debug, Clone)]
+pub enum FieldIssue {
+    /// the whole reply wasn't a JSON object, usually truncated output
+    InvalidJson,
+    Missing,
+    Invalid(String),
+}
+
+impl FieldIssue {
+    /// Short code stored with each recorded fallback
+    pub fn reason(&self) -> &'static str {
+        match self {
+            FieldIssue::InvalidJson => "invalid_json",
+            FieldIssue::Missing => "missing",
+            FieldIssue::Invalid(_) => "invalid",
+        }
+    }
+
+    pub fn detail(&self) -> Option<&str> {
+        match self {
+            FieldIssue::Invalid(detail) => Some(detail),
+            _ => None,
+        }
+    }
+}
+
+/// The reply split into fields, each either usable or with the reason it isn't
+#[derive(Debug)]
+pub struct ValidatedAnalysis {
+    pub category: Result<String, FieldIssue>,
+    pub tags: Result<Vec<String>, FieldIssue>,
+    pub title: Result<String, FieldIssue>,
+    pub summary: Result<String, FieldIssue>,
+}
+
+impl ValidatedAnalysis {
+    /// `(field, issue)` for every field that failed validation
+    pub fn issues(&self) -> Vec<(&'static str, &FieldIssue)> {
+        let mut issues = Vec::new();
+
+        if let Err(issue) = &self.category {
+            issues.push(("category", issue));
+        }
+        if let Err(issue) = &self.tags {
+            issues.push(("tags", issue));
+        }
+        if let Err(issue) = &self.title {
+            issues.push(("title", issue));
+        }
+        if let Err(issue) = &self.summary {
+            issues.push(("summary", issue));
+        }
+
+        issues
+    }
+
+    /// Human readable problem list, sent back to the model when asking it to retry
+    pub fn describe_issues(&self) -> String {
+        self.issues()
+            .iter()
+            .map(|(field, issue)| match issue {
+                FieldIssue::InvalidJson => "the reply was not a complete JSON object".to_string(),
+                FieldIssue::Missing => format!("\"{}\" is missing or empty", field),
+                FieldIssue::Invalid(detail) => format!("\"{}\" is invalid: {}", field, detail),
+            })
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+}
+
+/// Parse and validate a raw analysis reply. `summary` is only required when one was asked for
+pub fn validate_analysis(text: &str, want_summary: bool) -> ValidatedAnalysis {
+    let Some(value) = parse_json_object(text) else {
+        return ValidatedAnalysis {
+            category: Err(FieldIssue::InvalidJson),
+            tags: Err(FieldIssue::InvalidJson),
+            title: Err(FieldIssue::InvalidJson),
+            summary: if want_summary {
+                Err(FieldIssue::InvalidJson)
+            } else {
+                Ok(String::new())
+            },
+        };
+    };
+
+    let category = string_field(&value, "category").and_then(|category| {
+        let normalized = category.to_lowercase().replace([' ', '-'], "_");
+        if CATEGORIES.contains(&normalized.as_str()) {
+            Ok(normalized)
+        } else {
+            Err(FieldIssue::Invalid(format!(
+                "\"{}\" is not one of {}",
+                category,
+                CATEGORIES.join(", ")
+            )))
+        }
+    });
+
+    let tags = match value.get("tags").and_then(|tags| tags.as_array()) {
+        Some(tags) => {
+            let tags = normalize_tags(tags.iter().filter_map(|tag| tag.as_str()));
+            if tags.is_empty() {
+                Err(FieldIssue::Missing)
+            } else {
+                Ok(tags)
+            }
+        }
+        None => Err(FieldIssue::Missing),
+    };
+
+    let summary = if want_summary {
+        string_field(&value, "summary").and_then(|summary| {
+            if summary.chars().count() > MAX_SUMMARY_CHARS {
+                Err(FieldIssue::Invalid(format!(
+                    "longer than {} characters, keep it to a few bullet points",
+                    MAX_SUMMARY_CHARS
+                )))
+            } else {
+                Ok(summary)
+            }
+        })
+    } else {
+        Ok(String::new())
+    };
+
+    ValidatedAnalysis {
+        category,
+        tags,
+        title: string_field(&value, "title"),
+        summary,
+    }
+}
+
+/// Lowercase, hyphenate and dedupe tags, dropping anything left empty
+pub fn normalize_tags<'a>(tags: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::new();
+
+    for tag in tags {
+        let mut cleaned = String::new();
+        for c in tag.trim().to_lowercase().chars() {
+            if c.is_alphanumeric() || matches!(c, '+' | '#' | '.' | '@') {
+                cleaned.push(c);
+            } else if !cleaned.ends_with('-') {
+                // whitespace, underscores and other punctuation all become a single hyphen
+                cleaned.push('-');
+            }
+        }
+
+        let cleaned = cleaned.trim_matches('-').to_string();
+        if !cleaned.is_empty() && !normalized.contains(&cleaned) {
+            normalized.push(cleaned);
+        }
+
+        if normalized.len() == MAX_TAGS {
+            break;
+        }
+    }
+
+    normalized
+}
+
+/// The value of a top-level string field in a JSON object that is still being generated,
+/// as far as it has arrived. A half-received escape sequence at the end is left out
+pub fn partial_string_field(text: &str, field: &str) -> Option<String> {
+    let object = &text[text.find('{')?..];
+
+    // only a string directly inside the outer object and followed by `:` is a key, so a tag
+    // that happens to read "summary" is passed over
+    let mut depth = 0;
+    let mut string_start = None;
+    let mut escaped = false;
+    for (index, c) in object.char_indices() {
+        if let Some(start) = string_start {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => {
+                    string_start = None;
+                    if depth != 1 || &object[start + 1..index] != field {
+                        continue;
+                    }
+                    let rest = object[index + 1..].trim_start();
+                    if let Some(value) = rest.strip_prefix(':') {
+                        return decode_partial_string(value.trim_start().strip_prefix('"')?);
+                    }
+                }
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => string_start = Some(index),
+            '{' | '[' => depth += 1,
+            '}' | ']' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+// the JSON string starting at `text`, up to its closing quote or as much as has arrived
+fn decode_partial_string(text: &str) -> Option<String> {
+    let mut chars = text.chars();
+    let mut decoded = String::new();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => break,
+            '\\' => {
+                let escaped = match chars.next() {
+                    Some('n') => '\n',
+                    Some('t') => '\t',
+                    Some('r') => '\r',
+                    Some('b') => '\u{8}',
+                    Some('f') => '\u{c}',
+                    Some('u') => match unicode_escape(&mut chars) {
+                        Some(c) => c,
+                        // the rest of the escape hasn't arrived yet
+                        None => break,
+                    },
+                    Some(other) => other,
+                    None => break,
+                };
+                decoded.push(escaped);
+            }
+            c => decoded.push(c),
+        }
+    }
+    Some(decoded)
+}
+
+// the character of a `\uXXXX` escape whose `\u` was just read, joining surrogate pairs.
+// `None` while it is incomplete, broken escapes become U+FFFD
+fn unicode_escape(chars: &mut std::str::Chars) -> Option<char> {
+    let code_unit = |chars: &mut std::str::Chars| {
+        let hex: String = chars.by_ref().take(4).collect();
+        (hex.chars().count() == 4).then(|| u32::from_str_radix(&hex, 16).ok())
+    };
+
+    let Some(high) = code_unit(chars)? else {
+        return Some(char::REPLACEMENT_CHARACTER);
+    };
+    if !(0xD800..0xDC00).contains(&high) {
+        return Some(char::from_u32(high).unwrap_or(char::REPLACEMENT_CHARACTER));
+    }
+
+    // the low half follows as another `\uXXXX`, anything else is left to be read as text
+    let mut ahead = chars.clone();
+    match (ahead.next(), ahead.next()) {
+        (Some('\\'), Some('u')) => *chars = ahead,
+        (None, _) | (Some('\\'), None) => return None,
+        _ => return Some(char::REPLACEMENT_CHARACTER),
+    }
+    let low = code_unit(chars)?.filter(|low| (0xDC00..0xE000).contains(low));
+    Some(
+        low.and_then(|low| char::from_u32(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)))
+            .unwrap_or(char::REPLACEMENT_CHARACTER),
+    )
+}
+
+fn parse_json_object(text: &str) -> Option<Value> {
+    let trimmed = text.trim();
+
+    if let Ok(value @ Value::Object(_)) = serde_json::from_str::<Value>(trimmed) {
+        return Some(value);
+    }
+
+    // models sometimes wrap the object in a code fence or a sentence
+    let start = trimmed.find('{')?;
+    let end = trimmed.rfind('}')?;
+    if start >= end {
+        return None;
+    }
+
+    match serde_json::from_str::<Value>(&trimmed[start..=end]) {
+        Ok(value @ Value::Object(_)) => Some(value),
+        _ => None,
+    }
+}
+
+fn string_field(value: &Value, field: &str) -> Result<String, FieldIssue> {
+    match value.get(field) {
+        None | Some(Value::Null) => Err(FieldIssue::Missing),
+        Some(Value::String(s)) if s.trim().is_empty() => Err(FieldIssue::Missing),
+        Some(Value::String(s)) => Ok(s.trim().to_string()),
+        Some(other) => Err(FieldIssue::Invalid(format!(
+            "expected a string, got {}",
+            other
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_summary_while_it_streams() {
+        let reply = r#"{"category": "url", "summary": "Line one\nsays \"hi\" and more"#;
+        assert_eq!(
+            partial_string_field(reply, "summary").as_deref(),
+            Some("Line one\nsays \"hi\" and more")
+        );
+        // a half-received escape is left for the next chunk
+        assert_eq!(
+            partial_string_field(r#"{"summary": "caf\u00"#, "summary").as_deref(),
+            Some("caf")
+        );
+        assert_eq!(
+            partial_string_field(r#"{"summary": "café", "tags": []}"#, "summary").as_deref(),
+            Some("café")
+        );
+        assert_eq!(partial_string_field(r#"{"summary"#, "summary"), None);
+    }
+
+    #[test]
+    fn skips_values_that_look_like_the_key() {
+        let reply = r#"{"tags": ["summary", "notes"], "title": "summary", "summary": "Found it"#;
+        assert_eq!(
+            partial_string_field(reply, "summary").as_deref(),
+            Some("Found it")
+        );
+        let nested = r#"{"meta": {"summary": "inner"}, "summary": "outer"}"#;
+        assert_eq!(
+            partial_string_field(nested, "summary").as_deref(),
+            Some("outer")
+        );
+    }
+
+    #[test]
+    fn joins_surrogate_pairs() {
+        let reply = r#"{"summary": "smile \ud83d\ude00 done"}"#;
+        assert_eq!(
+            partial_string_field(reply, "summary").as_deref(),
+            Some("smile 😀 done")
+        );
+        // only the high half has arrived so far
+        assert_eq!(
+            partial_string_field(r#"{"summary": "smile \ud83d"#, "summary").as_deref(),
+            Some("smile ")
+        );
+        assert_eq!(
+            partial_string_field(r#"{"summary": "smile \ud83d\ude"#, "summary").as_deref(),
+            Some("smile ")
+        );
+        // a lone half can't be shown
+        assert_eq!(
+            partial_string_field(r#"{"summary": "a \ud83d b"}"#, "summary").as_deref(),
+            Some("a \u{fffd} b")
+        );
+    }
+
+    #[test]
+    fn accepts_a_complete_reply() {
+        let reply = r#"```json
+{"category": "Error Log", "tags": ["Rust", "panic"], "title": " Index out of bounds ", "summary": "- it panicked"}
+```"#;
+        let validated = validate_analysis(reply, true);
+
+        assert!(validated.issues().is_empty());
+        assert_eq!(validated.category.unwrap(), "error_log");
+        assert_eq!(validated.tags.unwrap(), ["rust", "panic"]);
+        assert_eq!(validated.title.unwrap(), "Index out of bounds");
+        assert_eq!(validated.summary.unwrap(), "- it panicked");
+    }
+
+    #[test]
+    fn rejects_an_unknown_category() {
+        let reply = r#"{"category": "recipes", "tags": ["food"], "title": "Soup", "summary": ""}"#;
+        let validated = validate_analysis(reply, false);
+
+        assert!(matches!(validated.category, Err(FieldIssue::Invalid(_))));
+        assert_eq!(validated.issues().len(), 1);
+        assert!(validated
+            .describe_issues()
+            .contains("\"recipes\" is not one of"));
+    }
+
+    #[test]
+    fn requires_a_summary_only_when_asked() {
+        let reply = r#"{"category": "notes", "tags": ["todo"], "title": "Todo", "summary": "  "}"#;
+        assert!(matches!(
+            validate_analysis(reply, true).summary,
+            Err(FieldIssue::Missing)
+        ));
+        assert_eq!(validate_analysis(reply, false).summary.unwrap(), "");
+    }
+
+    #[test]
+    fn rejects_a_summary_that_is_too_long() {
+        let reply = json!({
+            "category": "notes",
+            "tags": ["todo"],
+            "title": "Todo",
+            "summary": "- again\n".repeat(500),
+        })
+        .to_string();
+        let validated = validate_analysis(&reply, true);
+
+        assert!(matches!(validated.summary, Err(FieldIssue::Invalid(_))));
+        // not asked for, so not checked
+        assert!(validate_analysis(&reply, false).issues().is_empty());
+    }
+
+    #[test]
+    fn reports_missing_fields() {
+        let validated = validate_analysis(r#"{"category": "notes", "tags": []}"#, true);
+        let fields: Vec<&str> = validated.issues().iter().map(|(field, _)| *field).collect();
+        assert_eq!(fields, ["tags", "title", "summary"]);
+        assert!(matches!(validated.tags, Err(FieldIssue::Missing)));
+
+        let validated =
+            validate_analysis(r#"{"category": "notes", "tags": ["a"], "title": 3}"#, false);
+        assert!(matches!(validated.title, Err(FieldIssue::Invalid(_))));
+    }
+
+    #[test]
+    fn truncated_reply_is_invalid_json() {
+        let validated = validate_analysis(r#"{"category": "notes", "tags": ["a"#, true);
+        assert_eq!(validated.issues().len(), 4);
+        assert!(validated
+            .issues()
+            .iter()
+            .all(|(_, issue)| matches!(issue, FieldIssue::InvalidJson)));
+    }
+
+    #[test]
+    fn normalizes_and_dedupes_tags() {
+        assert_eq!(
+            normalize_tags([
+                "Rust",
+                " rust ",
+                "Error Handling",
+                "error_handling",
+                "C++",
+                "--"
+            ]),
+            ["rust", "error-handling", "c++"]
+        );
+        assert_eq!(normalize_tags(["a", "b", "c", "d", "e"]).len(), MAX_TAGS);
+    }
+}
@@ -0,0 +1,291 @@
+use super::provider::TokenUsage;
+use crate::settings::{SettingsManager, SettingsManagerState};
+use crate::AppState;
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, State};
+
+// USD per million input and output tokens, matched by the longest model name prefix.
+// `llm_prices` entries win over these, ollama runs locally and is always free
+const PRICES: &[(&str, f64, f64)] = &[
+    ("gpt-4o", 2.5, 10.0),
+    ("gpt-4o-mini", 0.15, 0.6),
+    ("gpt-4.1", 2.0, 8.0),
+    ("gpt-4.1-mini", 0.4, 1.6),
+    ("gpt-4.1-nano", 0.1, 0.4),
+    ("claude-opus", 15.0, 75.0),
+    ("claude-sonnet", 3.0, 15.0),
+    ("claude-haiku", 1.0, 5.0),
+    ("claude-3-5-haiku", 0.8, 4.0),
+    ("text-embedding-3-small", 0.02, 0.0),
+    ("text-embedding-3-large", 0.13, 0.0),
+];
+
+/// Pricing and monthly budget, from the `llm_prices` and `llm_budget_*` settings
+#[derive(Debug, Clone, Default)]
+pub struct UsageConfig {
+    prices: HashMap<String, (f64, f64)>,
+    pub soft_limit_usd: Option<f64>,
+    pub hard_limit_usd: Option<f64>,
+}
+
+impl UsageConfig {
+    pub fn from_settings(settings: &SettingsManager) -> Self {
+        let limit = |key: &str| {
+            settings
+                .get_setting(key)
+                .and_then(|value| value.trim().parse::<f64>().ok())
+                .filter(|limit| *limit > 0.0)
+        };
+
+        let prices = settings
+            .get_setting("llm_prices")
+            .and_then(|json| serde_json::from_str::<HashMap<String, (f64, f64)>>(&json).ok())
+            .unwrap_or_default();
+
+        Self {
+            prices,
+            soft_limit_usd: limit("llm_budget_monthly_soft_usd"),
+            hard_limit_usd: limit("llm_budget_monthly_hard_usd"),
+        }
+    }
+
+    /// Estimated cost in USD, zero for local and unknown models
+    pub fn cost(&self, provider: &str, model: &str, usage: TokenUsage) -> f64 {
+        if provider == "ollama" {
+            return 0.0;
+        }
+
+        let custom = self
+            .prices
+            .iter()
+            .map(|(prefix, &(input, output))| (prefix.as_str(), input, output));
+        let (input, output) = PRICES
+            .iter()
+            .copied()
+            // max_by_key keeps the last of equals, so configured prices win a tie
+            .chain(custom)
+            .filter(|(prefix, _, _)| model.starts_with(prefix))
+            .max_by_key(|(prefix, _, _)| prefix.len())
+            .map(|(_, input, output)| (input, output))
+            .unwrap_or((0.0, 0.0));
+
+        (usage.input_tokens as f64 * input + usage.output_tokens as f64 * output) / 1_000_000.0
+    }
+}
+
+/// One LLM call, successful or not
+pub struct UsageRecord<'a> {
+    pub task: &'a str,
+    pub provider: &'a str,
+    pub model: &'a str,
+    pub usage: Option<TokenUsage>,
+    pub latency: Duration,
+    /// ok | error | cancelled
+    pub status: &'a str,
+}
+
+pub fn record_usage(db_path: &Path, config: &UsageConfig, record: &UsageRecord) {
+    let usage = record.usage.unwrap_or_default();
+    let cost = config.cost(record.provider, record.model, usage);
+
+    let result = Connection::open(db_path).and_then(|conn| {
+        conn.execute(
+            r#"
+            INSERT INTO llm_usage (task, provider, model, input_tokens, output_tokens, latency_ms, cost_usd, status)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+            params![
+                record.task,
+                record.provider,
+                record.model,
+                usage.input_tokens,
+                usage.output_tokens,
+                record.latency.as_millis() as i64,
+                cost,
+                record.status
+            ],
+        )
+    });
+
+    if let Err(e) = result {
+        eprintln!("Failed to record LLM usage: {}", e);
+    }
+}
+
+/// Estimated spend since the start of the current month (UTC)
+pub fn month_to_date_cost(conn: &Connection) -> rusqlite::Result<f64> {
+    conn.query_row(
+        "SELECT COALESCE(SUM(cost_usd), 0) FROM llm_usage WHERE created_at >= datetime('now', 'start of month')",
+        [],
+        |row| row.get(0),
+    )
+}
+
+/// The hard limit, if this month's spend has reached it
+pub fn hard_limit_reached(db_path: &Path, config: &UsageConfig) -> Option<f64> {
+    let limit = config.hard_limit_usd?;
+
+    match Connection::open(db_path).and_then(|conn| month_to_date_cost(&conn)) {
+        Ok(spent) if spent >= limit => Some(limit),
+        Ok(_) => None,
+        Err(e) => {
+            eprintln!("Failed to check LLM budget: {}", e);
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BudgetWarning {
+    /// soft | hard
+    pub level: &'static str,
+    pub spent_usd: f64,
+    pub limit_usd: f64,
+}
+
+// the month and level already warned about, so each limit is announced once a month
+static LAST_WARNING: Mutex<Option<(String, &'static str)>> = Mutex::new(None);
+
+/// Emit `llm-budget-warning` the first time this month's spend crosses a limit
+pub fn warn_if_over_budget(app_handle: &AppHandle, db_path: &Path, config: &UsageConfig) {
+    if config.soft_limit_usd.is_none() && config.hard_limit_usd.is_none() {
+        return;
+    }
+
+    let result = Connection::open(db_path).and_then(|conn| {
+        let month: String =
+            conn.query_row("SELECT strftime('%Y-%m', 'now')", [], |row| row.get(0))?;
+        Ok((month, month_to_date_cost(&conn)?))
+    });
+    let (month, spent) = match result {
+        Ok(result) => result,
+        Err(e) => {
+            eprintln!("Failed to check LLM budget: {}", e);
+            return;
+        }
+    };
+
+    let warning = match (config.soft_limit_usd, config.hard_limit_usd) {
+        (_, Some(limit)) if spent >= limit => BudgetWarning {
+            level: "hard",
+            spent_usd: spent,
+            limit_usd: limit,
+        },
+        (Some(limit), _) if spent >= limit => BudgetWarning {
+            level: "soft",
+            spent_usd: spent,
+            limit_usd: limit,
+        },
+        _ => return,
+    };
+
+    let mut last = LAST_WARNING.lock().unwrap();
+    if last.as_ref() == Some(&(month.clone(), warning.level)) {
+        return;
+    }
+    *last = Some((month, warning.level));
+    drop(last);
+
+    eprintln!(
+        "LLM spend ${:.2} reached the {} monthly limit of ${:.2}",
+        warning.spent_usd, warning.level, warning.limit_usd
+    );
+    let _ = app_handle.emit("llm-budget-warning", &warning);
+}
+
+#[derive(Debug, Serialize)]
+pub struct UsageRow {
+    pub day: String,
+    pub task: String,
+    pub provider: String,
+    pub model: String,
+    pub calls: i64,
+    pub errors: i64,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    pub cost_usd: f64,
+    pub avg_latency_ms: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UsageReport {
+    /// identifies this install when reports from several seats are added up
+    pub seat_id: String,
+    pub seat_label: String,
+    pub month_to_date_usd: f64,
+    pub soft_limit_usd: Option<f64>,
+    pub hard_limit_usd: Option<f64>,
+    pub rows: Vec<UsageRow>,
+}
+
+/// LLM calls, tokens and estimated cost over the last `days` days (default 30), per day and task
+#[tauri::command]
+pub async fn get_usage_report(
+    state: State<'_, AppState>,
+    settings: State<'_, SettingsManagerState>,
+    days: Option<u32>,
+) -> Result<UsageReport, String> {
+    let conn =
+        Connection::open(&state.db_path).map_err(|e| format!("Failed to open database: {e}"))?;
+
+    let mut stmt = conn
+        .prepare(
+            r#"
+        SELECT
+          date(created_at),
+          task,
+          provider,
+          model,
+          COUNT(*),
+          COUNT(*) FILTER (WHERE status = 'error'),
+          SUM(input_tokens),
+          SUM(output_tokens),
+          SUM(cost_usd),
+          AVG(latency_ms)
+        FROM llm_usage
+        WHERE created_at >= datetime('now', ?)
+        GROUP BY date(created_at), task, provider, model
+        ORDER BY date(created_at) DESC, SUM(cost_usd) DESC
+        "#,
+        )
+        .map_err(|e| format!("Failed to prepare statement: {e}"))?;
+
+    let window = format!("-{} days", days.unwrap_or(30));
+
+    let rows = stmt
+        .query_map(params![window], |row| {
+            Ok(UsageRow {
+                day: row.get(0)?,
+                task: row.get(1)?,
+                provider: row.get(2)?,
+                model: row.get(3)?,
+                calls: row.get(4)?,
+                errors: row.get(5)?,
+                input_tokens: row.get(6)?,
+                output_tokens: row.get(7)?,
+                cost_usd: row.get(8)?,
+                avg_latency_ms: row.get(9)?,
+            })
+        })
+        .map_err(|e| format!("Failed to execute query: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to process row: {e}"))?;
+
+    let month_to_date_usd =
+        month_to_date_cost(&conn).map_err(|e| format!("Failed to read spend: {e}"))?;
+    let config = UsageConfig::from_settings(&settings.0);
+
+    Ok(UsageReport {
+        seat_id: settings.0.get_setting("seat_id").unwrap_or_default(),
+        seat_label: settings.0.get_setting("seat_label").unwrap_or_default(),
+        month_to_date_usd,
+        soft_limit_usd: config.soft_limit_usd,
+        hard_limit_usd: config.hard_limit_usd,
+        rows,
+    })
+}
@@ -0,0 +1,340 @@
+use crate::settings::SettingsManager;
+use regex::{Captures, Regex};
+use std::collections::HashMap;
+use std::net::Ipv6Addr;
+use std::sync::OnceLock;
+
+/// Which personal data gets swapped for placeholders before text is sent to the LLM
+#[derive(Debug, Clone)]
+pub struct PiiConfig {
+    pub enabled: bool,
+    pub names: Vec<String>,
+}
+
+impl PiiConfig {
+    pub fn from_settings(settings: &SettingsManager) -> Self {
+        let enabled = settings
+            .get_setting("pii_redaction")
+            .map(|value| value != "false")
+            .unwrap_or(true);
+
+        let names = settings
+            .get_setting("pii_names")
+            .and_then(|json| match serde_json::from_str::<Vec<String>>(&json) {
+                Ok(names) => Some(names),
+                Err(e) => {
+                    eprintln!("Invalid pii_names setting: {}", e);
+                    None
+                }
+            })
+            .unwrap_or_default()
+            .into_iter()
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty())
+            .collect();
+
+        Self { enabled, names }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum PiiKind {
+    Email,
+    Phone,
+    Ip,
+    Name,
+}
+
+impl PiiKind {
+    fn label(&self) -> &'static str {
+        match self {
+            PiiKind::Email => "EMAIL",
+            PiiKind::Phone => "PHONE",
+            PiiKind::Ip => "IP",
+            PiiKind::Name => "NAME",
+        }
+    }
+
+    fn from_label(label: &str) -> Option<Self> {
+        match label.to_uppercase().as_str() {
+            "EMAIL" => Some(PiiKind::Email),
+            "PHONE" => Some(PiiKind::Phone),
+            "IP" => Some(PiiKind::Ip),
+            "NAME" => Some(PiiKind::Name),
+            _ => None,
+        }
+    }
+}
+
+fn builtin_patterns() -> &'static [(PiiKind, Regex)] {
+    static PATTERNS: OnceLock<Vec<(PiiKind, Regex)>> = OnceLock::new();
+
+    PATTERNS.get_or_init(|| {
+        let pattern = |kind, re: &str| (kind, Regex::new(re).expect("invalid pii pattern"));
+
+        vec![
+            pattern(
+                PiiKind::Email,
+                r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}\b",
+            ),
+            pattern(
+                PiiKind::Ip,
+                r"\b(?:(?:25[0-5]|2[0-4]\d|1?\d?\d)\.){3}(?:25[0-5]|2[0-4]\d|1?\d?\d)\b",
+            ),
+            pattern(
+                PiiKind::Ip,
+                r"\b(?:[0-9A-Fa-f]{1,4}:){7}[0-9A-Fa-f]{1,4}\b|\b(?:[0-9A-Fa-f]{1,4}:){1,6}:(?:[0-9A-Fa-f]{1,4}(?::[0-9A-Fa-f]{1,4}){0,5})?\b",
+            ),
+            // a bare run of digits is more often an order id or a timestamp, so without a
+            // country code the groups have to be separated
+            pattern(
+                PiiKind::Phone,
+                r"\+\d{1,3}(?:[\s.-]?(?:\(\d{1,4}\)|\d{2,4})){2,4}\b",
+            ),
+            pattern(
+                PiiKind::Phone,
+                r"(?:\(\d{2,4}\)\s?|\b\d{3}[\s.-])\d{3}[\s.-]\d{4}\b",
+            ),
+        ]
+    })
+}
+
+fn placeholder_regex() -> &'static Regex {
+    static PLACEHOLDER: OnceLock<Regex> = OnceLock::new();
+    PLACEHOLDER
+        .get_or_init(|| Regex::new(r"(?i)<(EMAIL|PHONE|IP|NAME)_(\d+)>").expect("invalid regex"))
+}
+
+// the IPv6 pattern also matches code like `Add::add` or the `f32::` of `f32::abs`
+fn is_ipv6(text: &str, m: regex::Match) -> bool {
+    let is_path_char = |c: char| c.is_alphanumeric() || c == '_' || c == ':';
+    let before = text[..m.start()].chars().next_back();
+    let after = text[m.end()..].chars().next();
+
+    m.as_str().parse::<Ipv6Addr>().is_ok()
+        && m.as_str().contains(|c: char| c.is_ascii_digit())
+        && !before.is_some_and(is_path_char)
+        && !after.is_some_and(is_path_char)
+}
+
+/// How much of a reply that is still streaming in can be restored already. A `<` near the
+/// end may start a placeholder whose other half hasn't arrived yet
+pub fn restorable_len(text: &str) -> usize {
+    // longer than any placeholder, e.g. `<PHONE_123>`
+    const MAX_PLACEHOLDER_LEN: usize = 16;
+
+    match text.rfind('<') {
+        Some(start) if !text[start..].contains('>') && text.len() - start < MAX_PLACEHOLDER_LEN => {
+            start
+        }
+        _ => text.len(),
+    }
+}
+
+/// Replaces personal data with stable placeholders like `<EMAIL_1>` and puts
+/// the originals back into whatever the LLM returns
+pub struct PiiRedactor {
+    enabled: bool,
+    name_pattern: Option<Regex>,
+    // (kind, normalized original) -> placeholder number
+    assigned: HashMap<(PiiKind, String), usize>,
+    // (kind, placeholder number) -> original text
+    originals: HashMap<(PiiKind, usize), String>,
+}
+
+impl PiiRedactor {
+    pub fn new(config: &PiiConfig) -> Self {
+        let name_pattern = if config.names.is_empty() {
+            None
+        } else {
+            let alternatives: Vec<String> = config.names.iter().map(|n| regex::escape(n)).collect();
+            Regex::new(&format!(r"(?i)\b(?:{})\b", alternatives.join("|"))).ok()
+        };
+
+        Self {
+            enabled: config.enabled,
+            name_pattern,
+            assigned: HashMap::new(),
+            originals: HashMap::new(),
+        }
+    }
+
+    /// Swap every email, phone number, IP address and configured name for a placeholder,
+    /// the same value always gets the same placeholder
+    pub fn redact(&mut self, text: &str) -> String {
+        if !self.enabled {
+            return text.to_string();
+        }
+
+        let mut spans: Vec<(usize, usize, PiiKind)> = Vec::new();
+
+        let name_pattern = self.name_pattern.as_ref().map(|re| (PiiKind::Name, re));
+        let patterns = builtin_patterns().iter().map(|(kind, re)| (*kind, re));
+
+        for (kind, regex) in name_pattern.into_iter().chain(patterns) {
+            for m in regex.find_iter(text) {
+                if kind == PiiKind::Ip && m.as_str().contains(':') && !is_ipv6(text, m) {
+                    continue;
+                }
+                let overlaps = spans
+                    .iter()
+                    .any(|(start, end, _)| m.start() < *end && *start < m.end());
+                if !overlaps {
+                    spans.push((m.start(), m.end(), kind));
+                }
+            }
+        }
+
+        spans.sort_by_key(|(start, _, _)| *start);
+
+        let mut redacted = String::with_capacity(text.len());
+        let mut last = 0;
+
+        for (start, end, kind) in spans {
+            redacted.push_str(&text[last..start]);
+            redacted.push_str(&self.placeholder_for(kind, &text[start..end]));
+            last = end;
+        }
+        redacted.push_str(&text[last..]);
+
+        redacted
+    }
+
+    /// Put the original values back in place of any placeholders in `text`
+    pub fn restore(&self, text: &str) -> String {
+        if self.originals.is_empty() {
+            return text.to_string();
+        }
+
+        placeholder_regex()
+            .replace_all(text, |caps: &Captures| {
+                let original = PiiKind::from_label(&caps[1])
+                    .zip(caps[2].parse::<usize>().ok())
+                    .and_then(|key| self.originals.get(&key));
+
+                match original {
+                    Some(original) => original.clone(),
+                    None => caps[0].to_string(),
+                }
+            })
+            .into_owned()
+    }
+
+    fn placeholder_for(&mut self, kind: PiiKind, original: &str) -> String {
+        let key = (kind, original.to_lowercase());
+
+        let number = match self.assigned.get(&key) {
+            Some(number) => *number,
+            None => {
+                let number = self.assigned.keys().filter(|(k, _)| *k == kind).count() + 1;
+                self.assigned.insert(key, number);
+                self.originals.insert((kind, number), original.to_string());
+                number
+            }
+        };
+
+        format!("<{}_{}>", kind.label(), number)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redactor(names: &[&str]) -> PiiRedactor {
+        PiiRedactor::new(&PiiConfig {
+            enabled: true,
+            names: names.iter().map(|name| name.to_string()).collect(),
+        })
+    }
+
+    #[test]
+    fn redacts_and_restores_round_trip() {
+        let mut redactor = redactor(&["Ada Lovelace"]);
+        let text = "Ada Lovelace <ada@example.com> called +44 20 7946 0958 from 192.168.1.20";
+
+        let redacted = redactor.redact(text);
+        assert_eq!(
+            redacted,
+            "<NAME_1> <<EMAIL_1>> called <PHONE_1> from <IP_1>"
+        );
+        assert_eq!(redactor.restore(&redacted), text);
+    }
+
+    #[test]
+    fn numbers_repeated_values_stably() {
+        let mut redactor = redactor(&[]);
+
+        let first = redactor.redact("mail a@x.io, b@x.io and A@X.io");
+        assert_eq!(first, "mail <EMAIL_1>, <EMAIL_2> and <EMAIL_1>");
+        // later texts of the same request reuse the numbers
+        assert_eq!(redactor.redact("cc b@x.io"), "cc <EMAIL_2>");
+        // the model may change the case of a placeholder, unknown ones are left alone
+        assert_eq!(
+            redactor.restore("<email_2> and <EMAIL_9>"),
+            "b@x.io and <EMAIL_9>"
+        );
+    }
+
+    #[test]
+    fn leaves_rust_paths_alone() {
+        let mut redactor = redactor(&[]);
+        for code in [
+            "fn main() -> std::io::Result<()>",
+            "let x = f32::abs(y);",
+            "impl Add::add for Face::beef",
+            "use a::b::c;",
+        ] {
+            assert_eq!(redactor.redact(code), code);
+        }
+    }
+
+    #[test]
+    fn redacts_real_ipv6_addresses() {
+        let mut redactor = redactor(&[]);
+        assert_eq!(
+            redactor.redact("ping 2001:db8::8a2e:370:7334 or fe80::1."),
+            "ping <IP_1> or <IP_2>."
+        );
+    }
+
+    #[test]
+    fn leaves_bare_digit_runs_alone() {
+        let mut redactor = redactor(&[]);
+        let text = "order 1234567890 at 1700000000";
+        assert_eq!(redactor.redact(text), text);
+    }
+
+    #[test]
+    fn disabled_redactor_changes_nothing() {
+        let mut redactor = PiiRedactor::new(&PiiConfig {
+            enabled: false,
+            names: Vec::new(),
+        });
+        assert_eq!(redactor.redact("a@x.io"), "a@x.io");
+    }
+
+    #[test]
+    fn holds_back_a_placeholder_split_across_chunks() {
+        let mut redactor = redactor(&[]);
+        redactor.redact("write to a@x.io");
+
+        // as the reply streams in, `<EMAIL_1>` arrives in two pieces
+        let mut streamed = String::from("Write to <EMA");
+        let end = restorable_len(&streamed);
+        assert_eq!(&streamed[..end], "Write to ");
+
+        streamed.push_str("IL_1> today");
+        let end = restorable_len(&streamed);
+        assert_eq!(end, streamed.len());
+        assert_eq!(redactor.restore(&streamed), "Write to a@x.io today");
+    }
+
+    #[test]
+    fn does_not_hold_back_a_plain_less_than() {
+        assert_eq!(restorable_len("if a < b"), "if a ".len());
+        let text = "if a < b then the rest of a long sentence";
+        assert_eq!(restorable_len(text), text.len());
+        assert_eq!(restorable_len("a <b> c"), "a <b> c".len());
+    }
+}
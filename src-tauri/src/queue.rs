@@ -0,0 +1,796 @@
+use crate::archive::{self, ArchiveRules};
+use crate::expiry;
+use crate::extract::PageContent;
+use crate::fetch::{self, FetchConfig, FetchedPage, PageFetcher};
+use crate::llm::chunking::estimate_tokens;
+use crate::llm::embeddings::Embedder;
+use crate::llm::jobs::InFlightJobsState;
+use crate::llm::usage::{self, UsageConfig};
+use crate::llm::{ClipAnalysis, LlmClient, LlmError};
+use crate::reprocess::{self, BatchOutcome};
+use crate::secrets;
+use crate::settings::{SettingsManager, SettingsManagerState};
+use crate::shortcut::{is_url, Clip};
+use crate::AppState;
+use rusqlite::{params, Connection, OptionalExtension, TransactionBehavior};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::sync::Notify;
+use tokio_util::sync::CancellationToken;
+
+// idle workers look for jobs whose retry delay ran out this often
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+const MAX_ATTEMPTS: i64 = 5;
+const RETRY_BASE_SECS: i64 = 30;
+const RETRY_MAX_SECS: i64 = 3600;
+
+/// Wakes the workers whenever a job is queued
+#[derive(Default)]
+pub struct AiQueue {
+    wake: Notify,
+}
+
+pub struct AiQueueState(pub Arc<AiQueue>);
+
+impl AiQueue {
+    pub fn wake(&self) {
+        self.wake.notify_waiters();
+    }
+}
+
+struct AiJob {
+    id: i64,
+    clip_id: i64,
+    attempts: i64,
+    batch_id: Option<i64>,
+}
+
+/// Queue a clip for categorization, tagging and summarization
+pub fn enqueue_analysis(
+    app_handle: &AppHandle,
+    db_path: &Path,
+    clip_id: i64,
+) -> rusqlite::Result<i64> {
+    let conn = open(db_path)?;
+    conn.execute(
+        "INSERT INTO ai_jobs (clip_id, kind) VALUES (?, 'analyze')",
+        params![clip_id],
+    )?;
+    let job_id = conn.last_insert_rowid();
+
+    app_handle.state::<AiQueueState>().0.wake();
+
+    Ok(job_id)
+}
+
+/// Spawn the worker pool. Jobs left `running` by a previous session are picked up again
+pub fn start_ai_workers(app_handle: AppHandle, db_path: PathBuf) -> rusqlite::Result<()> {
+    let requeued = open(&db_path)?.execute(
+        "UPDATE ai_jobs SET status = 'queued', attempts = MAX(attempts - 1, 0) WHERE status = 'running'",
+        [],
+    )?;
+    if requeued > 0 {
+        println!("Resuming {} interrupted AI job(s)", requeued);
+    }
+
+    let workers = worker_count(&app_handle.state::<SettingsManagerState>().0);
+    let queue = app_handle.state::<AiQueueState>().0.clone();
+
+    for _ in 0..workers {
+        let app_handle = app_handle.clone();
+        let db_path = db_path.clone();
+        let queue = queue.clone();
+        tauri::async_runtime::spawn(async move {
+            run_worker(app_handle, db_path, queue).await;
+        });
+    }
+
+    Ok(())
+}
+
+fn worker_count(settings: &SettingsManager) -> usize {
+    settings
+        .get_setting("ai_workers")
+        .and_then(|value| value.trim().parse::<usize>().ok())
+        .unwrap_or(2)
+        .clamp(1, 8)
+}
+
+async fn run_worker(app_handle: AppHandle, db_path: PathBuf, queue: Arc<AiQueue>) {
+    loop {
+        // register for wake-ups before looking, so a job queued in between isn't missed
+        let notified = queue.wake.notified();
+        tokio::pin!(notified);
+        notified.as_mut().enable();
+
+        match claim_next_job(&db_path) {
+            Ok(Some(job)) => {
+                run_job(&app_handle, &db_path, job).await;
+                continue;
+            }
+            Ok(None) => {}
+            Err(e) => eprintln!("Failed to claim AI job: {}", e),
+        }
+
+        let _ = tokio::time::timeout(POLL_INTERVAL, notified).await;
+    }
+}
+
+fn open(db_path: &Path) -> rusqlite::Result<Connection> {
+    let conn = Connection::open(db_path)?;
+    // several workers write at once, wait for each other instead of failing with SQLITE_BUSY
+    conn.busy_timeout(Duration::from_secs(5))?;
+    Ok(conn)
+}
+
+fn claim_next_job(db_path: &Path) -> rusqlite::Result<Option<AiJob>> {
+    open(db_path)?
+        .query_row(
+            r#"
+        UPDATE ai_jobs
+        SET status = 'running', attempts = attempts + 1, updated_at = CURRENT_TIMESTAMP
+        WHERE id = (
+            SELECT id FROM ai_jobs
+            WHERE status = 'queued' AND run_after <= CURRENT_TIMESTAMP
+            -- fresh captures go ahead of bulk reprocessing
+            ORDER BY batch_id IS NOT NULL, id
+            LIMIT 1
+        )
+        RETURNING id, clip_id, attempts, batch_id
+        "#,
+            [],
+            |row| {
+                Ok(AiJob {
+                    id: row.get(0)?,
+                    clip_id: row.get(1)?,
+                    attempts: row.get(2)?,
+                    batch_id: row.get(3)?,
+                })
+            },
+        )
+        .optional()
+}
+
+async fn run_job(app_handle: &AppHandle, db_path: &Path, job: AiJob) {
+    let clip = match load_clip(db_path, job.clip_id) {
+        Ok(Some(clip)) => clip,
+        Ok(None) => {
+            // the clip was deleted while its job waited
+            finish_job(app_handle, db_path, &job, BatchOutcome::Skipped);
+            return;
+        }
+        Err(e) => {
+            eprintln!("Failed to load clip {} for AI job: {}", job.clip_id, e);
+            retry_or_fail(app_handle, db_path, &job, &e.to_string(), None);
+            return;
+        }
+    };
+
+    // clips saved by hand or before secret scanning existed never went through the capture check
+    if !secrets::scan_clip(&clip).is_empty() {
+        eprintln!(
+            "Clip {} contains secrets, not sending it to the LLM",
+            job.clip_id
+        );
+        if let Err(e) = open(db_path).and_then(|conn| {
+            conn.execute(
+                "UPDATE clips SET ai_status = 'skipped' WHERE id = ?",
+                params![job.clip_id],
+            )
+        }) {
+            eprintln!("Failed to update clip {}: {}", job.clip_id, e);
+        }
+        finish_job(app_handle, db_path, &job, BatchOutcome::Skipped);
+        return;
+    }
+
+    let settings = app_handle.state::<SettingsManagerState>();
+    let jobs = app_handle.state::<InFlightJobsState>();
+    let handle = jobs.0.start(app_handle, job.id, job.clip_id, "analyze");
+    let llm = LlmClient::from_settings(&settings.0, db_path)
+        .with_cancel(handle.token.clone())
+        .with_summary_stream(handle.stream_sink());
+
+    let fetched = match &clip {
+        Clip::Text { plain } if is_url(plain) => {
+            fetch_page(
+                db_path,
+                &settings.0,
+                job.clip_id,
+                plain.trim(),
+                &handle.token,
+            )
+            .await
+        }
+        _ => None,
+    };
+
+    // archiving needs no LLM, so it happens whatever the analysis comes to. A category rule
+    // is checked again once the analysis has settled the category
+    let archived = match &fetched {
+        Some(fetched) => {
+            archive_if_wanted(db_path, &settings.0, job.clip_id, fetched, &handle.token).await
+        }
+        None => false,
+    };
+
+    // a page without readable text says no more than its URL
+    let page = fetched
+        .as_ref()
+        .map(|(_, content)| content)
+        .filter(|content| !content.text.is_empty() || content.title.is_some());
+
+    let want_summary = should_summarize(&settings.0, &clip);
+    let result = llm.analyze_clip(&clip, want_summary, page).await;
+
+    usage::warn_if_over_budget(
+        app_handle,
+        db_path,
+        &UsageConfig::from_settings(&settings.0),
+    );
+
+    match result {
+        Ok(analysis) => {
+            if let Err(e) = apply_analysis(app_handle, db_path, job.clip_id, &analysis, "done") {
+                eprintln!("Failed to store AI result for clip {}: {}", job.clip_id, e);
+                retry_or_fail(app_handle, db_path, &job, &e.to_string(), None);
+                return;
+            }
+            if let Some(fetched) = fetched.as_ref().filter(|_| !archived) {
+                archive_if_wanted(db_path, &settings.0, job.clip_id, fetched, &handle.token).await;
+            }
+            embed_clip(db_path, &settings.0, job.clip_id, &handle.token).await;
+            finish_job(app_handle, db_path, &job, BatchOutcome::Succeeded);
+
+            println!(
+                "Clip {} analyzed as: {} with tags: {:?}",
+                job.clip_id, analysis.category, analysis.tags
+            );
+            let _ = app_handle.emit("clip-updated", job.clip_id.to_string());
+        }
+        Err(LlmError::BudgetExceeded(_)) if job.batch_id.is_some() => {
+            // a reprocessed clip keeps its earlier results rather than getting offline ones
+            finish_job(app_handle, db_path, &job, BatchOutcome::Skipped);
+        }
+        Err(LlmError::BudgetExceeded(_)) => {
+            // out of budget for the month, categorize locally instead of retrying
+            let analysis = ClipAnalysis::fallback(&clip, want_summary);
+            if let Err(e) = apply_analysis(app_handle, db_path, job.clip_id, &analysis, "offline") {
+                eprintln!(
+                    "Failed to store offline result for clip {}: {}",
+                    job.clip_id, e
+                );
+                retry_or_fail(app_handle, db_path, &job, &e.to_string(), None);
+                return;
+            }
+            if let Some(fetched) = fetched.as_ref().filter(|_| !archived) {
+                archive_if_wanted(db_path, &settings.0, job.clip_id, fetched, &handle.token).await;
+            }
+            embed_clip(db_path, &settings.0, job.clip_id, &handle.token).await;
+            finish_job(app_handle, db_path, &job, BatchOutcome::Succeeded);
+            let _ = app_handle.emit("clip-updated", job.clip_id.to_string());
+        }
+        Err(LlmError::Cancelled) => {
+            if let Err(e) = cancel_job(app_handle, db_path, &job) {
+                eprintln!("Failed to cancel AI job {}: {}", job.id, e);
+            }
+        }
+        Err(LlmError::CircuitOpen(retry_in)) => {
+            // AI is paused, wait it out without using up an attempt. zero means another
+            // request is probing, so back off a little instead of spinning on the job
+            retry_or_fail(
+                app_handle,
+                db_path,
+                &job,
+                "AI paused after repeated failures",
+                Some((retry_in as i64).max(5)),
+            );
+        }
+        Err(e) => {
+            eprintln!("AI job {} failed: {}", job.id, e);
+            retry_or_fail(app_handle, db_path, &job, &e.to_string(), None);
+        }
+    }
+}
+
+/// Fetch the page behind a URL clip and store what was found. `None` when fetching is
+/// turned off, fails or is cancelled, the URL alone is analyzed then
+async fn fetch_page(
+    db_path: &Path,
+    settings: &SettingsManager,
+    clip_id: i64,
+    url: &str,
+    token: &CancellationToken,
+) -> Option<(FetchedPage, PageContent)> {
+    let config = FetchConfig::from_settings(settings);
+    if !config.enabled {
+        return None;
+    }
+
+    let fetcher = match PageFetcher::new(config) {
+        Ok(fetcher) => fetcher,
+        Err(e) => {
+            eprintln!("Failed to set up page fetcher: {}", e);
+            return None;
+        }
+    };
+
+    let result = tokio::select! {
+        _ = token.cancelled() => return None,
+        result = fetcher.fetch(url) => result,
+    };
+    let result = result.map(|page| {
+        let content = page.content();
+        (page, content)
+    });
+
+    match &result {
+        Ok((page, _)) => println!(
+            "Fetched {} for clip {}{}",
+            page.final_url,
+            clip_id,
+            if page.truncated { " (truncated)" } else { "" }
+        ),
+        Err(e) => eprintln!("Failed to fetch {} for clip {}: {}", url, clip_id, e),
+    }
+
+    // oEmbed only fills in what the page's own meta tags leave out, so failing to read it
+    // costs nothing but a plainer preview
+    let oembed_url = result.as_ref().ok().and_then(|(page, _)| page.oembed_url());
+    let oembed = match oembed_url {
+        Some(oembed_url) => {
+            let oembed = tokio::select! {
+                _ = token.cancelled() => return None,
+                oembed = fetcher.fetch_oembed(oembed_url.as_str()) => oembed,
+            };
+            oembed
+                .map_err(|e| eprintln!("Failed to read oEmbed for clip {}: {}", clip_id, e))
+                .ok()
+        }
+        None => None,
+    };
+
+    let saved = open(db_path).and_then(|conn| {
+        fetch::save_link_page(&conn, clip_id, url, &result)?;
+        // a failed fetch leaves the last good preview in place
+        match &result {
+            Ok((page, _)) => {
+                let metadata = page.metadata(oembed.as_ref());
+                fetch::save_link_metadata(&conn, clip_id, &page.final_url, &metadata)
+            }
+            Err(_) => Ok(()),
+        }
+    });
+    if let Err(e) = saved {
+        eprintln!("Failed to store page info for clip {}: {}", clip_id, e);
+    }
+
+    result.ok()
+}
+
+/// Save an offline copy of a fetched page if the archive settings cover the clip's URL or
+/// the category it has so far. Failing to archive doesn't fail the job. Returns whether
+/// the page was archived
+async fn archive_if_wanted(
+    db_path: &Path,
+    settings: &SettingsManager,
+    clip_id: i64,
+    (page, content): &(FetchedPage, PageContent),
+    token: &CancellationToken,
+) -> bool {
+    let rules = ArchiveRules::from_settings(settings);
+    let stored = open(db_path).and_then(|conn| {
+        conn.query_row(
+            r#"
+            SELECT clips.category, link_pages.url
+            FROM clips JOIN link_pages ON link_pages.clip_id = clips.id
+            WHERE clips.id = ?
+            "#,
+            params![clip_id],
+            |row| Ok((row.get::<_, Option<String>>(0)?, row.get::<_, String>(1)?)),
+        )
+    });
+    let (category, url) = match stored {
+        Ok(stored) => stored,
+        Err(e) => {
+            eprintln!("Failed to load clip {} for archiving: {}", clip_id, e);
+            return false;
+        }
+    };
+    if !rules.matches(&url, category.as_deref()) {
+        return false;
+    }
+
+    let fetcher = match PageFetcher::new(FetchConfig::from_settings(settings)) {
+        Ok(fetcher) => fetcher,
+        Err(e) => {
+            eprintln!("Failed to set up page fetcher: {}", e);
+            return false;
+        }
+    };
+
+    let result = tokio::select! {
+        _ = token.cancelled() => return false,
+        result = archive::archive_page(db_path, clip_id, &fetcher, page, content, rules.max_images) => result,
+    };
+    match result {
+        Ok(images) => {
+            println!("Archived clip {} with {} image(s)", clip_id, images);
+            true
+        }
+        Err(e) => {
+            eprintln!("Failed to archive clip {}: {}", clip_id, e);
+            false
+        }
+    }
+}
+
+/// Embed a clip for semantic search once its title and summary are in. Failing to embed
+/// doesn't fail the job, `build_embeddings` catches up later
+async fn embed_clip(
+    db_path: &Path,
+    settings: &SettingsManager,
+    clip_id: i64,
+    token: &CancellationToken,
+) {
+    let Some(embedder) = Embedder::from_settings(settings, db_path) else {
+        return;
+    };
+
+    let clip_ids = [clip_id];
+    let result = tokio::select! {
+        _ = token.cancelled() => return,
+        result = embedder.embed_clips(Some(&clip_ids)) => result,
+    };
+    if let Err(e) = result {
+        eprintln!("Failed to embed clip {}: {}", clip_id, e);
+    }
+}
+
+// links and images always get a summary, other text only if the `summarize_text` policy says so
+fn should_summarize(settings: &SettingsManager, clip: &Clip) -> bool {
+    let plain = match clip {
+        Clip::Text { plain } if !is_url(plain) => plain,
+        _ => return true,
+    };
+
+    match settings.get_setting("summarize_text").as_deref() {
+        Some("always") => true,
+        Some("long") => {
+            let min_tokens = settings
+                .get_setting("summarize_min_tokens")
+                .and_then(|value| value.trim().parse::<usize>().ok())
+                .unwrap_or(800);
+            estimate_tokens(plain) >= min_tokens
+        }
+        _ => false,
+    }
+}
+
+fn load_clip(db_path: &Path, clip_id: i64) -> rusqlite::Result<Option<Clip>> {
+    let clip_json: Option<String> = open(db_path)?
+        .query_row(
+            "SELECT clip FROM clips WHERE id = ?",
+            params![clip_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+
+    Ok(clip_json.and_then(|json| {
+        let value = serde_json::from_str(&json).ok()?;
+        Clip::from_stored(&value)
+    }))
+}
+
+fn apply_analysis(
+    app_handle: &AppHandle,
+    db_path: &Path,
+    clip_id: i64,
+    analysis: &ClipAnalysis,
+    ai_status: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut conn = open(db_path)?;
+    // lock out edits between reading which fields are manual and writing the rest
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+    let manual_json: Option<String> = tx
+        .query_row(
+            "SELECT manual_fields FROM clips WHERE id = ?",
+            params![clip_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+    let Some(manual_json) = manual_json else {
+        return Ok(());
+    };
+    let manual: Vec<String> = serde_json::from_str(&manual_json).unwrap_or_default();
+    let unless_manual =
+        |field: &str, value: String| (!manual.iter().any(|m| m == field)).then_some(value);
+
+    let category = unless_manual("category", analysis.category.clone());
+
+    // the real category may have an expiry rule the placeholder one didn't
+    let expires_in = category.as_deref().and_then(|category| {
+        expiry::expiry_for_category(&app_handle.state::<SettingsManagerState>().0, category)
+    });
+
+    tx.execute(
+        r#"
+        UPDATE clips
+        SET category = COALESCE(?, category),
+            tags = COALESCE(?, tags),
+            title = COALESCE(?, title),
+            summary = COALESCE(?, summary),
+            ai_status = ?,
+            expires_at = COALESCE(expires_at, datetime(created_at, ?))
+        WHERE id = ?
+        "#,
+        params![
+            category,
+            unless_manual("tags", serde_json::to_string(&analysis.tags)?),
+            unless_manual("title", analysis.title.clone()),
+            unless_manual("summary", analysis.summary.clone()),
+            ai_status,
+            expiry::expiry_modifier(expires_in),
+            clip_id
+        ],
+    )?;
+
+    tx.commit()?;
+    Ok(())
+}
+
+/// Remove a finished job and count it towards its reprocess batch, if any
+fn finish_job(app_handle: &AppHandle, db_path: &Path, job: &AiJob, outcome: BatchOutcome) {
+    let result = open(db_path).and_then(|conn| {
+        conn.execute("DELETE FROM ai_jobs WHERE id = ?", params![job.id])?;
+        match job.batch_id {
+            Some(batch_id) => reprocess::record_batch_result(app_handle, &conn, batch_id, outcome),
+            None => Ok(()),
+        }
+    });
+
+    if let Err(e) = result {
+        eprintln!("Failed to finish AI job {}: {}", job.id, e);
+    }
+}
+
+fn retry_or_fail(
+    app_handle: &AppHandle,
+    db_path: &Path,
+    job: &AiJob,
+    error: &str,
+    delay_secs: Option<i64>,
+) {
+    let result = match delay_secs {
+        Some(delay) => schedule_retry(db_path, job, error, delay, false),
+        None if job.attempts < MAX_ATTEMPTS => {
+            let delay = (RETRY_BASE_SECS << (job.attempts - 1).clamp(0, 10)).min(RETRY_MAX_SECS);
+            schedule_retry(db_path, job, error, delay, true)
+        }
+        None => fail_job(app_handle, db_path, job, error).map(|_| {
+            let _ = app_handle.emit("clip-updated", job.clip_id.to_string());
+        }),
+    };
+
+    if let Err(e) = result {
+        eprintln!("Failed to update AI job {}: {}", job.id, e);
+    }
+}
+
+fn schedule_retry(
+    db_path: &Path,
+    job: &AiJob,
+    error: &str,
+    delay_secs: i64,
+    counts_as_attempt: bool,
+) -> rusqlite::Result<()> {
+    open(db_path)?.execute(
+        r#"
+        UPDATE ai_jobs
+        SET status = 'queued', last_error = ?, run_after = datetime('now', ?),
+            attempts = attempts - ?, updated_at = CURRENT_TIMESTAMP
+        WHERE id = ?
+        "#,
+        params![
+            error,
+            format!("+{} seconds", delay_secs),
+            if counts_as_attempt { 0 } else { 1 },
+            job.id
+        ],
+    )?;
+    Ok(())
+}
+
+fn fail_job(
+    app_handle: &AppHandle,
+    db_path: &Path,
+    job: &AiJob,
+    error: &str,
+) -> rusqlite::Result<()> {
+    eprintln!(
+        "AI job {} gave up after {} attempts: {}",
+        job.id, job.attempts, error
+    );
+
+    let conn = open(db_path)?;
+    conn.execute(
+        "UPDATE ai_jobs SET status = 'failed', last_error = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+        params![error, job.id],
+    )?;
+    // a reprocessed clip keeps its earlier results, only a first analysis counts as failed
+    conn.execute(
+        "UPDATE clips SET ai_status = 'failed' WHERE id = ? AND ai_status = 'pending'",
+        params![job.clip_id],
+    )?;
+
+    if let Some(batch_id) = job.batch_id {
+        reprocess::record_batch_result(app_handle, &conn, batch_id, BatchOutcome::Failed)?;
+    }
+    Ok(())
+}
+
+fn cancel_job(app_handle: &AppHandle, db_path: &Path, job: &AiJob) -> rusqlite::Result<()> {
+    let conn = open(db_path)?;
+    conn.execute("DELETE FROM ai_jobs WHERE id = ?", params![job.id])?;
+    conn.execute(
+        "UPDATE clips SET ai_status = 'cancelled' WHERE id = ? AND ai_status = 'pending'",
+        params![job.clip_id],
+    )?;
+
+    if let Some(batch_id) = job.batch_id {
+        reprocess::record_batch_result(app_handle, &conn, batch_id, BatchOutcome::Skipped)?;
+    }
+
+    let _ = app_handle.emit("clip-updated", job.clip_id.to_string());
+    Ok(())
+}
+
+/// Cancel one AI job, whether it is running or still waiting in the queue
+#[tauri::command]
+pub fn cancel_ai_job(
+    app_handle: AppHandle,
+    state: State<'_, AppState>,
+    jobs: State<'_, InFlightJobsState>,
+    job_id: i64,
+) -> Result<bool, String> {
+    // a running job cleans up after itself once its request is aborted
+    if jobs.0.cancel(job_id) {
+        return Ok(true);
+    }
+
+    let conn = open(&state.db_path).map_err(|e| format!("Failed to open database: {e}"))?;
+
+    let removed: Option<(i64, Option<i64>)> = conn
+        .query_row(
+            "DELETE FROM ai_jobs WHERE id = ? AND status = 'queued' RETURNING clip_id, batch_id",
+            params![job_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .map_err(|e| format!("Failed to cancel job: {e}"))?;
+
+    let Some((clip_id, batch_id)) = removed else {
+        return Ok(false);
+    };
+
+    if let Some(batch_id) = batch_id {
+        reprocess::record_batch_result(&app_handle, &conn, batch_id, BatchOutcome::Skipped)
+            .map_err(|e| format!("Failed to update batch: {e}"))?;
+    }
+
+    conn.execute(
+        "UPDATE clips SET ai_status = 'cancelled' WHERE id = ? AND ai_status = 'pending'",
+        params![clip_id],
+    )
+    .map_err(|e| format!("Failed to update clip: {e}"))?;
+
+    let _ = app_handle.emit("clip-updated", clip_id.to_string());
+
+    Ok(true)
+}
+
+/// Cancel every queued and running AI job, returns how many were cancelled
+#[tauri::command]
+pub fn cancel_all_ai_jobs(
+    app_handle: AppHandle,
+    state: State<'_, AppState>,
+    jobs: State<'_, InFlightJobsState>,
+) -> Result<usize, String> {
+    let running = jobs.0.cancel_all();
+
+    let conn = open(&state.db_path).map_err(|e| format!("Failed to open database: {e}"))?;
+
+    reprocess::cancel_running_batches(&app_handle, &conn)
+        .map_err(|e| format!("Failed to cancel reprocess batches: {e}"))?;
+
+    let mut stmt = conn
+        .prepare("DELETE FROM ai_jobs WHERE status = 'queued' RETURNING clip_id")
+        .map_err(|e| format!("Failed to prepare statement: {e}"))?;
+    let clip_ids = stmt
+        .query_map([], |row| row.get::<_, i64>(0))
+        .map_err(|e| format!("Failed to cancel jobs: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to cancel jobs: {e}"))?;
+
+    for clip_id in &clip_ids {
+        conn.execute(
+            "UPDATE clips SET ai_status = 'cancelled' WHERE id = ? AND ai_status = 'pending'",
+            params![clip_id],
+        )
+        .map_err(|e| format!("Failed to update clip: {e}"))?;
+
+        let _ = app_handle.emit("clip-updated", clip_id.to_string());
+    }
+
+    Ok(running + clip_ids.len())
+}
+
+#[derive(Debug, Serialize)]
+pub struct AiQueueStatus {
+    pub queued: i64,
+    pub running: i64,
+    pub failed: i64,
+}
+
+#[tauri::command]
+pub fn get_ai_queue_status(state: State<'_, AppState>) -> Result<AiQueueStatus, String> {
+    let conn = open(&state.db_path).map_err(|e| format!("Failed to open database: {e}"))?;
+
+    conn.query_row(
+        r#"
+        SELECT
+          COUNT(*) FILTER (WHERE status = 'queued'),
+          COUNT(*) FILTER (WHERE status = 'running'),
+          COUNT(*) FILTER (WHERE status = 'failed')
+        FROM ai_jobs
+        "#,
+        [],
+        |row| {
+            Ok(AiQueueStatus {
+                queued: row.get(0)?,
+                running: row.get(1)?,
+                failed: row.get(2)?,
+            })
+        },
+    )
+    .map_err(|e| format!("Failed to read queue status: {e}"))
+}
+
+/// Put every failed job back in the queue, e.g. after fixing the API key
+#[tauri::command]
+pub fn retry_failed_ai_jobs(
+    state: State<'_, AppState>,
+    queue: State<'_, AiQueueState>,
+) -> Result<usize, String> {
+    let conn = open(&state.db_path).map_err(|e| format!("Failed to open database: {e}"))?;
+
+    // a failed reprocess leaves the earlier analysis in place, that still counts as done
+    conn.execute(
+        r#"
+        UPDATE clips SET ai_status = 'pending'
+        WHERE ai_status = 'failed'
+          AND id IN (SELECT clip_id FROM ai_jobs WHERE status = 'failed')
+        "#,
+        [],
+    )
+    .map_err(|e| format!("Failed to update clips: {e}"))?;
+
+    let requeued = conn
+        .execute(
+            r#"
+            UPDATE ai_jobs
+            SET status = 'queued', attempts = 0, run_after = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP
+            WHERE status = 'failed'
+            "#,
+            [],
+        )
+        .map_err(|e| format!("Failed to requeue jobs: {e}"))?;
+
+    queue.0.wake();
+
+    Ok(requeued)
+}
@@ -0,0 +1,172 @@
+use crate::llm::embeddings;
+use crate::AppState;
+use rusqlite::Connection;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use tauri::State;
+use url::Url;
+
+// how much each signal adds to the relevance score at most
+const TAG_WEIGHT: f64 = 1.0;
+const DOMAIN_WEIGHT: f64 = 0.6;
+const TIME_WEIGHT: f64 = 0.5;
+const SIMILARITY_WEIGHT: f64 = 1.2;
+// clips captured within this many seconds of each other probably belong to the same task
+const CAPTURE_WINDOW_SECS: f64 = 30.0 * 60.0;
+// below this cosine similarity embeddings say little about relatedness
+const MIN_SIMILARITY: f64 = 0.4;
+
+/// A clip related to the one being viewed, with why it is
+#[derive(Debug, Clone, Serialize)]
+pub struct RelatedItem {
+    pub id: String,
+    pub score: f64,
+    /// one short sentence per signal that matched, strongest first
+    pub reasons: Vec<String>,
+}
+
+struct ClipSignals {
+    id: i64,
+    tags: HashSet<String>,
+    domain: Option<String>,
+    captured_at: f64,
+}
+
+/// The `limit` clips most related to `clip_id` by shared tags, the same site, being captured
+/// around the same time and, for embedded clips, similar meaning
+pub fn related_items(
+    db_path: &Path,
+    clip_id: i64,
+    limit: usize,
+) -> rusqlite::Result<Vec<RelatedItem>> {
+    let conn = Connection::open(db_path)?;
+
+    let mut stmt = conn.prepare(
+        "SELECT id, tags, canonical_url, CAST(strftime('%s', created_at) AS REAL) FROM clips",
+    )?;
+    let clips = stmt
+        .query_map([], |row| {
+            let tags: Vec<String> =
+                serde_json::from_str(&row.get::<_, Option<String>>(1)?.unwrap_or_default())
+                    .unwrap_or_default();
+            Ok(ClipSignals {
+                id: row.get(0)?,
+                tags: tags
+                    .iter()
+                    .map(|tag| tag.trim().to_lowercase())
+                    .filter(|tag| !tag.is_empty())
+                    .collect(),
+                domain: row
+                    .get::<_, Option<String>>(2)?
+                    .and_then(|url| Url::parse(&url).ok())
+                    .and_then(|url| url.host_str().map(str::to_string)),
+                captured_at: row.get::<_, Option<f64>>(3)?.unwrap_or_default(),
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let Some(target) = clips.iter().find(|clip| clip.id == clip_id) else {
+        return Err(rusqlite::Error::QueryReturnedNoRows);
+    };
+
+    // only vectors from the same model are comparable
+    let similarities: HashMap<i64, f64> = match embeddings::load_vector(&conn, clip_id)? {
+        Some((model, vector)) => embeddings::load_vectors(&conn, &model)?
+            .into_iter()
+            .map(|(id, other)| (id, embeddings::similarity(&vector, &other)))
+            .collect(),
+        None => HashMap::new(),
+    };
+
+    let mut related: Vec<RelatedItem> = clips
+        .iter()
+        .filter(|clip| clip.id != clip_id)
+        .filter_map(|clip| relate(target, clip, similarities.get(&clip.id).copied()))
+        .collect();
+    related.sort_by(|a, b| b.score.total_cmp(&a.score));
+    related.truncate(limit);
+
+    Ok(related)
+}
+
+fn relate(
+    target: &ClipSignals,
+    clip: &ClipSignals,
+    similarity: Option<f64>,
+) -> Option<RelatedItem> {
+    let mut signals: Vec<(f64, String)> = Vec::new();
+
+    let mut shared: Vec<&String> = target.tags.intersection(&clip.tags).collect();
+    if !shared.is_empty() {
+        shared.sort();
+        let union = target.tags.union(&clip.tags).count();
+        let tags = shared
+            .iter()
+            .map(|tag| tag.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        signals.push((
+            TAG_WEIGHT * shared.len() as f64 / union as f64,
+            format!("Shares tags: {}", tags),
+        ));
+    }
+
+    if let (Some(domain), Some(other)) = (&target.domain, &clip.domain) {
+        if domain == other {
+            signals.push((DOMAIN_WEIGHT, format!("Also from {}", domain)));
+        }
+    }
+
+    let apart = (target.captured_at - clip.captured_at).abs();
+    if apart < CAPTURE_WINDOW_SECS {
+        signals.push((
+            TIME_WEIGHT * (1.0 - apart / CAPTURE_WINDOW_SECS),
+            format!("Captured {} apart", describe_duration(apart)),
+        ));
+    }
+
+    if let Some(similarity) = similarity.filter(|similarity| *similarity >= MIN_SIMILARITY) {
+        signals.push((
+            SIMILARITY_WEIGHT * (similarity - MIN_SIMILARITY) / (1.0 - MIN_SIMILARITY),
+            format!("Similar content ({:.0}% match)", similarity * 100.0),
+        ));
+    }
+
+    if signals.is_empty() {
+        return None;
+    }
+    signals.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    Some(RelatedItem {
+        id: clip.id.to_string(),
+        score: signals.iter().map(|(score, _)| score).sum(),
+        reasons: signals.into_iter().map(|(_, reason)| reason).collect(),
+    })
+}
+
+fn describe_duration(secs: f64) -> String {
+    match secs.round() as u64 {
+        0..=59 => "less than a minute".to_string(),
+        60..=119 => "a minute".to_string(),
+        secs => format!("{} minutes", secs / 60),
+    }
+}
+
+/// Clips related to the given one, most related first, each with the reasons it was picked
+#[tauri::command]
+pub fn get_related_items(
+    state: State<'_, AppState>,
+    clip_id: String,
+    limit: Option<usize>,
+) -> Result<Vec<RelatedItem>, String> {
+    let clip_id = clip_id
+        .parse::<i64>()
+        .map_err(|_| "Invalid item id".to_string())?;
+
+    match related_items(&state.db_path, clip_id, limit.unwrap_or(10).clamp(1, 50)) {
+        Ok(related) => Ok(related),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Err("Item not found".to_string()),
+        Err(e) => Err(format!("Failed to find related items: {e}")),
+    }
+}
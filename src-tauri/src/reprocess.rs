@@ -0,0 +1,285 @@
+use crate::llm::jobs::InFlightJobsState;
+use crate::queue::AiQueueState;
+use crate::AppState;
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, State};
+
+/// Which clips to re-run AI over. Every condition given must match
+#[derive(Debug, Default, Deserialize)]
+pub struct ReprocessFilter {
+    pub ids: Option<Vec<String>>,
+    pub category: Option<String>,
+    /// inclusive bounds in the `created_at` format, e.g. `2025-01-31` or `2025-01-31 18:00:00`
+    pub created_after: Option<String>,
+    pub created_before: Option<String>,
+    pub ai_status: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchProgress {
+    pub batch_id: i64,
+    pub total: i64,
+    pub succeeded: i64,
+    pub failed: i64,
+    pub skipped: i64,
+    /// running | completed | cancelled
+    pub status: String,
+}
+
+pub enum BatchOutcome {
+    Succeeded,
+    Failed,
+    Skipped,
+}
+
+impl BatchOutcome {
+    fn column(&self) -> &'static str {
+        match self {
+            BatchOutcome::Succeeded => "succeeded",
+            BatchOutcome::Failed => "failed",
+            BatchOutcome::Skipped => "skipped",
+        }
+    }
+}
+
+fn progress_from_row(row: &rusqlite::Row) -> rusqlite::Result<BatchProgress> {
+    Ok(BatchProgress {
+        batch_id: row.get(0)?,
+        total: row.get(1)?,
+        succeeded: row.get(2)?,
+        failed: row.get(3)?,
+        skipped: row.get(4)?,
+        status: row.get(5)?,
+    })
+}
+
+/// Count one finished job towards its batch and emit progress, or completion once all are in
+pub fn record_batch_result(
+    app_handle: &AppHandle,
+    conn: &Connection,
+    batch_id: i64,
+    outcome: BatchOutcome,
+) -> rusqlite::Result<()> {
+    let column = outcome.column();
+
+    // a cancelled batch already reported completion, late results are dropped
+    let progress = conn
+        .query_row(
+            &format!(
+                r#"
+            UPDATE ai_batches SET {column} = {column} + 1
+            WHERE id = ? AND status = 'running'
+            RETURNING id, total, succeeded, failed, skipped, status
+            "#
+            ),
+            params![batch_id],
+            progress_from_row,
+        )
+        .optional()?;
+
+    let Some(mut progress) = progress else {
+        return Ok(());
+    };
+
+    if progress.succeeded + progress.failed + progress.skipped < progress.total {
+        let _ = app_handle.emit("reprocess-progress", &progress);
+        return Ok(());
+    }
+
+    conn.execute(
+        "UPDATE ai_batches SET status = 'completed', finished_at = CURRENT_TIMESTAMP WHERE id = ?",
+        params![batch_id],
+    )?;
+    progress.status = "completed".to_string();
+
+    println!(
+        "Reprocess batch {} finished: {} succeeded, {} failed, {} skipped",
+        batch_id, progress.succeeded, progress.failed, progress.skipped
+    );
+    let _ = app_handle.emit("reprocess-completed", &progress);
+
+    Ok(())
+}
+
+/// Mark every running batch cancelled, used when the whole queue is cleared
+pub fn cancel_running_batches(app_handle: &AppHandle, conn: &Connection) -> rusqlite::Result<()> {
+    let mut stmt = conn.prepare(
+        r#"
+        UPDATE ai_batches SET status = 'cancelled', finished_at = CURRENT_TIMESTAMP
+        WHERE status = 'running'
+        RETURNING id, total, succeeded, failed, skipped, status
+        "#,
+    )?;
+    let cancelled = stmt
+        .query_map([], progress_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    for progress in cancelled {
+        let _ = app_handle.emit("reprocess-completed", &progress);
+    }
+
+    Ok(())
+}
+
+/// Re-run categorization and summarization over existing clips in the background.
+/// Clips holding secrets are never included, and fields the user set by hand are kept.
+/// Returns `None` when nothing matches the filter
+#[tauri::command]
+pub fn reprocess_items(
+    app_handle: AppHandle,
+    state: State<'_, AppState>,
+    queue: State<'_, AiQueueState>,
+    filter: ReprocessFilter,
+) -> Result<Option<BatchProgress>, String> {
+    let mut conn =
+        Connection::open(&state.db_path).map_err(|e| format!("Failed to open database: {e}"))?;
+
+    // secrets stay local no matter what the filter asks for
+    let mut conditions = vec![
+        "ai_status != 'skipped'".to_string(),
+        "sensitive = 0".to_string(),
+        // a clip already waiting in the queue gets fresh results from that job
+        "id NOT IN (SELECT clip_id FROM ai_jobs WHERE status IN ('queued', 'running'))".to_string(),
+    ];
+    let mut values: Vec<Value> = Vec::new();
+
+    if let Some(ids) = &filter.ids {
+        let ids = ids
+            .iter()
+            .map(|id| id.parse::<i64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Invalid item id: {e}"))?;
+        if ids.is_empty() {
+            return Ok(None);
+        }
+
+        conditions.push(format!("id IN ({})", vec!["?"; ids.len()].join(", ")));
+        values.extend(ids.into_iter().map(Value::Integer));
+    }
+
+    for (condition, value) in [
+        ("category = ?", &filter.category),
+        ("created_at >= ?", &filter.created_after),
+        ("created_at <= ?", &filter.created_before),
+        ("ai_status = ?", &filter.ai_status),
+    ] {
+        if let Some(value) = value {
+            conditions.push(condition.to_string());
+            values.push(Value::Text(value.clone()));
+        }
+    }
+
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("Failed to start transaction: {e}"))?;
+
+    let clip_ids = {
+        let mut stmt = tx
+            .prepare(&format!(
+                "SELECT id FROM clips WHERE {} ORDER BY created_at DESC",
+                conditions.join(" AND ")
+            ))
+            .map_err(|e| format!("Failed to prepare statement: {e}"))?;
+
+        let ids = stmt
+            .query_map(params_from_iter(values), |row| row.get::<_, i64>(0))
+            .map_err(|e| format!("Failed to execute query: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to process row: {e}"))?;
+        ids
+    };
+
+    if clip_ids.is_empty() {
+        return Ok(None);
+    }
+
+    tx.execute(
+        "INSERT INTO ai_batches (total) VALUES (?)",
+        params![clip_ids.len() as i64],
+    )
+    .map_err(|e| format!("Failed to create batch: {e}"))?;
+    let batch_id = tx.last_insert_rowid();
+
+    for clip_id in &clip_ids {
+        tx.execute(
+            "INSERT INTO ai_jobs (clip_id, kind, batch_id) VALUES (?, 'analyze', ?)",
+            params![clip_id, batch_id],
+        )
+        .map_err(|e| format!("Failed to queue clip {clip_id}: {e}"))?;
+    }
+
+    tx.commit()
+        .map_err(|e| format!("Failed to commit transaction: {e}"))?;
+
+    queue.0.wake();
+
+    let progress = BatchProgress {
+        batch_id,
+        total: clip_ids.len() as i64,
+        succeeded: 0,
+        failed: 0,
+        skipped: 0,
+        status: "running".to_string(),
+    };
+    println!(
+        "Reprocessing {} clip(s) in batch {}",
+        progress.total, batch_id
+    );
+    let _ = app_handle.emit("reprocess-progress", &progress);
+
+    Ok(Some(progress))
+}
+
+/// Stop a reprocess run: queued clips are dropped and in-flight requests aborted
+#[tauri::command]
+pub fn cancel_reprocess(
+    app_handle: AppHandle,
+    state: State<'_, AppState>,
+    jobs: State<'_, InFlightJobsState>,
+    batch_id: i64,
+) -> Result<bool, String> {
+    let conn =
+        Connection::open(&state.db_path).map_err(|e| format!("Failed to open database: {e}"))?;
+
+    let progress = conn
+        .query_row(
+            r#"
+            UPDATE ai_batches SET status = 'cancelled', finished_at = CURRENT_TIMESTAMP
+            WHERE id = ? AND status = 'running'
+            RETURNING id, total, succeeded, failed, skipped, status
+            "#,
+            params![batch_id],
+            progress_from_row,
+        )
+        .optional()
+        .map_err(|e| format!("Failed to cancel batch: {e}"))?;
+
+    let Some(progress) = progress else {
+        return Ok(false);
+    };
+
+    conn.execute(
+        "DELETE FROM ai_jobs WHERE batch_id = ? AND status IN ('queued', 'failed')",
+        params![batch_id],
+    )
+    .map_err(|e| format!("Failed to remove queued jobs: {e}"))?;
+
+    let mut stmt = conn
+        .prepare("SELECT id FROM ai_jobs WHERE batch_id = ? AND status = 'running'")
+        .map_err(|e| format!("Failed to prepare statement: {e}"))?;
+    let running = stmt
+        .query_map(params![batch_id], |row| row.get::<_, i64>(0))
+        .map_err(|e| format!("Failed to execute query: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to process row: {e}"))?;
+
+    for job_id in running {
+        jobs.0.cancel(job_id);
+    }
+
+    let _ = app_handle.emit("reprocess-completed", &progress);
+
+    Ok(true)
+}
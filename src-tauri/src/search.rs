@@ -0,0 +1,281 @@
+use crate::llm::embeddings::{self, EmbedError, Embedder};
+use crate::settings::{SettingsManager, SettingsManagerState};
+use crate::AppState;
+use rusqlite::Connection;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use tauri::State;
+
+// dampens rank differences in reciprocal rank fusion, 60 is the usual choice
+const RRF_K: f64 = 60.0;
+// how deep into each ranking hybrid search looks, per result asked for
+const CANDIDATES_PER_RESULT: usize = 5;
+
+// how much a matching term counts in each field
+const TITLE_WEIGHT: f64 = 3.0;
+const TAG_WEIGHT: f64 = 2.0;
+const SUMMARY_WEIGHT: f64 = 1.5;
+const CONTENT_WEIGHT: f64 = 1.0;
+
+/// One search result, best first. The per-ranking scores are there to explain the order
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchHit {
+    pub id: String,
+    pub score: f64,
+    /// cosine similarity between the query and the clip, -1 to 1
+    pub semantic_score: Option<f64>,
+    /// term matches weighted by field and rarity, unbounded
+    pub keyword_score: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchMode {
+    Semantic,
+    Keyword,
+    Hybrid,
+}
+
+impl SearchMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "semantic" => Some(Self::Semantic),
+            "keyword" => Some(Self::Keyword),
+            "hybrid" => Some(Self::Hybrid),
+            _ => None,
+        }
+    }
+}
+
+/// The `k` clips that best match `query`. Hybrid search falls back to keywords alone when no
+/// embedding provider is configured, semantic search fails instead
+pub async fn search(
+    db_path: &Path,
+    settings: &SettingsManager,
+    query: &str,
+    k: usize,
+    mode: SearchMode,
+) -> Result<Vec<SearchHit>, EmbedError> {
+    let embedder = match mode {
+        SearchMode::Keyword => None,
+        _ => Embedder::from_settings(settings, db_path),
+    };
+
+    let semantic = match &embedder {
+        Some(embedder) => Some(semantic_ranking(db_path, embedder, query).await?),
+        None if mode == SearchMode::Semantic => {
+            return Err(EmbedError::NotConfigured);
+        }
+        None => None,
+    };
+    let keyword = match mode {
+        SearchMode::Semantic => None,
+        _ => Some(keyword_ranking(&Connection::open(db_path)?, query)?),
+    };
+
+    let hits = match (semantic, keyword) {
+        (Some(semantic), Some(keyword)) => {
+            let weight = settings
+                .get_setting("search_semantic_weight")
+                .and_then(|value| value.trim().parse::<f64>().ok())
+                .unwrap_or(0.5)
+                .clamp(0.0, 1.0);
+            fuse(&semantic, &keyword, weight, k * CANDIDATES_PER_RESULT)
+        }
+        (Some(semantic), None) => semantic
+            .into_iter()
+            .map(|(id, score)| hit(id, score, Some(score), None))
+            .collect(),
+        (None, Some(keyword)) => keyword
+            .into_iter()
+            .map(|(id, score)| hit(id, score, None, Some(score)))
+            .collect(),
+        (None, None) => Vec::new(),
+    };
+
+    Ok(hits.into_iter().take(k).collect())
+}
+
+fn hit(id: i64, score: f64, semantic_score: Option<f64>, keyword_score: Option<f64>) -> SearchHit {
+    SearchHit {
+        id: id.to_string(),
+        score,
+        semantic_score,
+        keyword_score,
+    }
+}
+
+// every embedded clip by similarity to the query, best first
+async fn semantic_ranking(
+    db_path: &Path,
+    embedder: &Embedder,
+    query: &str,
+) -> Result<Vec<(i64, f64)>, EmbedError> {
+    let query_vector = embedder
+        .embed("embed_query", &[query.to_string()])
+        .await?
+        .remove(0);
+    let vectors = embeddings::load_vectors(&Connection::open(db_path)?, &embedder.model_id())?;
+
+    let mut ranking: Vec<(i64, f64)> = vectors
+        .iter()
+        .map(|(id, vector)| (*id, embeddings::similarity(&query_vector, vector)))
+        .collect();
+    ranking.sort_by(|a, b| b.1.total_cmp(&a.1));
+    Ok(ranking)
+}
+
+// clips containing any query term, best first. each term scores ln(1 + count) in each field,
+// times the field weight and how rare the term is across the library
+fn keyword_ranking(conn: &Connection, query: &str) -> rusqlite::Result<Vec<(i64, f64)>> {
+    let terms: HashSet<String> = tokenize(query).collect();
+    if terms.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut stmt = conn.prepare(
+        r#"
+        SELECT
+          clips.id,
+          clips.title,
+          link_pages.title,
+          clips.tags,
+          clips.summary,
+          link_pages.description,
+          CASE WHEN json_extract(clips.clip, '$.type') = 'text'
+            THEN json_extract(clips.clip, '$.content') END
+        FROM clips
+        LEFT JOIN link_pages ON link_pages.clip_id = clips.id
+        "#,
+    )?;
+    let documents = stmt
+        .query_map([], |row| {
+            let text = |index: usize| -> rusqlite::Result<String> {
+                Ok(row.get::<_, Option<String>>(index)?.unwrap_or_default())
+            };
+            let tags: Vec<String> = serde_json::from_str(&text(3)?).unwrap_or_default();
+
+            Ok((
+                row.get::<_, i64>(0)?,
+                [
+                    (
+                        TITLE_WEIGHT,
+                        term_counts(&format!("{} {}", text(1)?, text(2)?)),
+                    ),
+                    (TAG_WEIGHT, term_counts(&tags.join(" "))),
+                    (
+                        SUMMARY_WEIGHT,
+                        term_counts(&format!("{} {}", text(4)?, text(5)?)),
+                    ),
+                    (CONTENT_WEIGHT, term_counts(&text(6)?)),
+                ],
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // documents each term appears in, for its rarity
+    let mut frequency: HashMap<&str, usize> = HashMap::new();
+    for (_, fields) in &documents {
+        for term in &terms {
+            if fields.iter().any(|(_, counts)| counts.contains_key(term)) {
+                *frequency.entry(term).or_default() += 1;
+            }
+        }
+    }
+    let total = documents.len() as f64;
+
+    let mut ranking: Vec<(i64, f64)> = documents
+        .iter()
+        .map(|(id, fields)| {
+            let score = terms
+                .iter()
+                .map(|term| {
+                    let df = frequency.get(term.as_str()).copied().unwrap_or(0) as f64;
+                    let idf = ((total + 1.0) / (df + 1.0)).ln() + 1.0;
+                    let matches: f64 = fields
+                        .iter()
+                        .map(|(weight, counts)| {
+                            weight * (1.0 + counts.get(term).copied().unwrap_or(0) as f64).ln()
+                        })
+                        .sum();
+                    matches * idf
+                })
+                .sum::<f64>();
+            (*id, score)
+        })
+        .filter(|(_, score)| *score > 0.0)
+        .collect();
+    ranking.sort_by(|a, b| b.1.total_cmp(&a.1));
+    Ok(ranking)
+}
+
+fn tokenize(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+}
+
+fn term_counts(text: &str) -> HashMap<String, usize> {
+    let mut counts = HashMap::new();
+    for term in tokenize(text) {
+        *counts.entry(term).or_default() += 1;
+    }
+    counts
+}
+
+// weighted reciprocal rank fusion of the top `depth` of both rankings. ranks combine well
+// where the raw scores don't, similarities and keyword scores live on different scales
+fn fuse(
+    semantic: &[(i64, f64)],
+    keyword: &[(i64, f64)],
+    semantic_weight: f64,
+    depth: usize,
+) -> Vec<SearchHit> {
+    let mut hits: HashMap<i64, SearchHit> = HashMap::new();
+
+    for (rank, (id, score)) in semantic.iter().take(depth).enumerate() {
+        let entry = hits.entry(*id).or_insert_with(|| hit(*id, 0.0, None, None));
+        entry.score += semantic_weight / (RRF_K + rank as f64 + 1.0);
+        entry.semantic_score = Some(*score);
+    }
+    for (rank, (id, score)) in keyword.iter().take(depth).enumerate() {
+        let entry = hits.entry(*id).or_insert_with(|| hit(*id, 0.0, None, None));
+        entry.score += (1.0 - semantic_weight) / (RRF_K + rank as f64 + 1.0);
+        entry.keyword_score = Some(*score);
+    }
+
+    let mut hits: Vec<SearchHit> = hits.into_values().collect();
+    hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+    hits
+}
+
+/// Find clips by meaning rather than exact words. `mode` is semantic (the default), keyword
+/// or hybrid, which blends both rankings by `search_semantic_weight`
+#[tauri::command]
+pub async fn semantic_search(
+    state: State<'_, AppState>,
+    settings: State<'_, SettingsManagerState>,
+    query: String,
+    k: Option<usize>,
+    mode: Option<String>,
+) -> Result<Vec<SearchHit>, String> {
+    let mode = match mode.as_deref() {
+        None => SearchMode::Semantic,
+        Some(mode) => {
+            SearchMode::parse(mode).ok_or_else(|| format!("Unknown search mode: {mode}"))?
+        }
+    };
+    if query.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+
+    search(
+        &state.db_path,
+        &settings.0,
+        query.trim(),
+        k.unwrap_or(10).clamp(1, 100),
+        mode,
+    )
+    .await
+    .map_err(|e| format!("Failed to search: {e}"))
+}
@@ -0,0 +1,308 @@
+use crate::settings::SettingsManager;
+use crate::shortcut::Clip;
+use regex::Regex;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// Kinds of credentials the local detector knows how to spot
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum SecretKind {
+    AwsAccessKey,
+    AwsSecretKey,
+    GithubToken,
+    Jwt,
+    PrivateKey,
+    UrlPassword,
+    PasswordAssignment,
+    HighEntropyToken,
+}
+
+impl SecretKind {
+    /// Tag attached to clips that contain this kind of secret
+    pub fn tag(&self) -> &'static str {
+        match self {
+            SecretKind::AwsAccessKey => "aws-access-key",
+            SecretKind::AwsSecretKey => "aws-secret-key",
+            SecretKind::GithubToken => "github-token",
+            SecretKind::Jwt => "jwt",
+            SecretKind::PrivateKey => "private-key",
+            SecretKind::UrlPassword => "url-password",
+            SecretKind::PasswordAssignment => "password",
+            SecretKind::HighEntropyToken => "token",
+        }
+    }
+}
+
+/// A secret found in a piece of text, `start..end` is the byte range of the secret itself
+#[derive(Debug, Clone)]
+pub struct SecretMatch {
+    pub kind: SecretKind,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// What to do with a capture that contains a secret, read from the `secret_policy` setting
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SecretPolicy {
+    /// keep the clip as-is but never send it to the LLM
+    SkipAi,
+    /// replace the secret with a placeholder before saving
+    Redact,
+    /// drop the capture entirely
+    Block,
+}
+
+impl SecretPolicy {
+    pub fn from_settings(settings: &SettingsManager) -> Self {
+        match settings.get_setting("secret_policy").as_deref() {
+            Some("redact") => SecretPolicy::Redact,
+            Some("block") => SecretPolicy::Block,
+            _ => SecretPolicy::SkipAi,
+        }
+    }
+}
+
+struct Pattern {
+    kind: SecretKind,
+    regex: Regex,
+    // capture group holding the secret, 0 when the whole match is the secret
+    group: usize,
+    // only report the match if the secret looks random enough
+    check_entropy: bool,
+}
+
+fn patterns() -> &'static [Pattern] {
+    static PATTERNS: OnceLock<Vec<Pattern>> = OnceLock::new();
+
+    PATTERNS.get_or_init(|| {
+        let pattern = |kind, re: &str, group, check_entropy| Pattern {
+            kind,
+            regex: Regex::new(re).expect("invalid secret pattern"),
+            group,
+            check_entropy,
+        };
+
+        vec![
+            pattern(
+                SecretKind::PrivateKey,
+                r"(?s)-----BEGIN [A-Z ]*PRIVATE KEY-----.*?(?:-----END [A-Z ]*PRIVATE KEY-----|\z)",
+                0,
+                false,
+            ),
+            pattern(
+                SecretKind::AwsAccessKey,
+                r"\b(?:AKIA|ASIA|AGPA|AIDA|AROA|ANPA|ANVA|AIPA)[0-9A-Z]{16}\b",
+                0,
+                false,
+            ),
+            pattern(
+                SecretKind::AwsSecretKey,
+                r#"(?i)aws.{0,20}?(?:secret|key).{0,20}?[\s'"=:]+([A-Za-z0-9/+=]{40})\b"#,
+                1,
+                false,
+            ),
+            pattern(
+                SecretKind::GithubToken,
+                r"\b(?:gh[pousr]_[A-Za-z0-9]{36,255}|github_pat_[A-Za-z0-9_]{22,255})\b",
+                0,
+                false,
+            ),
+            pattern(
+                SecretKind::Jwt,
+                r"\beyJ[A-Za-z0-9_-]{8,}\.eyJ[A-Za-z0-9_-]{8,}\.[A-Za-z0-9_-]{8,}",
+                0,
+                false,
+            ),
+            pattern(
+                SecretKind::UrlPassword,
+                r"\b[a-zA-Z][a-zA-Z0-9+.-]*://[^\s:/@]+:([^\s:/@]+)@",
+                1,
+                false,
+            ),
+            pattern(
+                SecretKind::PasswordAssignment,
+                r#"(?i)\b(?:password|passwd|pwd|pass)\b\s*[:=]\s*['"]?([^\s'"]{6,})"#,
+                1,
+                false,
+            ),
+            pattern(
+                SecretKind::HighEntropyToken,
+                r#"(?i)\b(?:api[_-]?key|secret|token|access[_-]?key|auth)\b\s*[:=]\s*['"]?([^\s'"]{16,})"#,
+                1,
+                true,
+            ),
+            pattern(
+                SecretKind::HighEntropyToken,
+                r"[A-Za-z0-9_\-+/=]{32,}",
+                0,
+                true,
+            ),
+        ]
+    })
+}
+
+/// Find every secret in `text`, ordered by position and without overlaps
+pub fn detect_secrets(text: &str) -> Vec<SecretMatch> {
+    let mut found: Vec<SecretMatch> = Vec::new();
+
+    for pattern in patterns() {
+        for caps in pattern.regex.captures_iter(text) {
+            let Some(secret) = caps.get(pattern.group) else {
+                continue;
+            };
+
+            if pattern.check_entropy && !looks_random(secret.as_str()) {
+                continue;
+            }
+
+            // earlier patterns are more specific, so they win on overlap
+            let overlaps = found
+                .iter()
+                .any(|m| secret.start() < m.end && m.start < secret.end());
+
+            if !overlaps {
+                found.push(SecretMatch {
+                    kind: pattern.kind,
+                    start: secret.start(),
+                    end: secret.end(),
+                });
+            }
+        }
+    }
+
+    found.sort_by_key(|m| m.start);
+    found
+}
+
+/// Secrets found in a captured clip, images are never scanned
+pub fn scan_clip(clip: &Clip) -> Vec<SecretMatch> {
+    match clip {
+        Clip::Text { plain } => detect_secrets(plain),
+        Clip::Image { .. } => Vec::new(),
+    }
+}
+
+/// Replace each secret with a `[REDACTED:<kind>]` placeholder
+pub fn redact_secrets(text: &str, matches: &[SecretMatch]) -> String {
+    let mut redacted = String::with_capacity(text.len());
+    let mut last = 0;
+
+    for m in matches {
+        redacted.push_str(&text[last..m.start]);
+        redacted.push_str(&format!("[REDACTED:{}]", m.kind.tag()));
+        last = m.end;
+    }
+    redacted.push_str(&text[last..]);
+
+    redacted
+}
+
+/// A short excerpt around `matches[index]` with every secret in it masked, safe to show in the UI
+pub fn masked_excerpt(text: &str, matches: &[SecretMatch], index: usize) -> String {
+    const CONTEXT: usize = 24;
+
+    let target = &matches[index];
+    let start = char_boundary_at_or_before(text, target.start.saturating_sub(CONTEXT));
+    let end = char_boundary_at_or_after(text, (target.end + CONTEXT).min(text.len()));
+
+    let mut excerpt = String::new();
+    if start > 0 {
+        excerpt.push('…');
+    }
+
+    let mut cursor = start;
+    for m in matches.iter().filter(|m| m.start < end && m.end > start) {
+        let secret_start = m.start.max(cursor);
+        let secret_end = m.end.min(end);
+
+        excerpt.push_str(&text[cursor..secret_start]);
+        // the type prefix only means something when the secret starts inside the excerpt
+        let kind = (secret_start == m.start).then_some(m.kind);
+        excerpt.push_str(&mask(&text[secret_start..secret_end], kind));
+        cursor = secret_end;
+    }
+    excerpt.push_str(&text[cursor..end]);
+
+    if end < text.len() {
+        excerpt.push('…');
+    }
+
+    excerpt.replace('\n', " ")
+}
+
+// everything but the fixed prefix of typed tokens like `AKIA` or `ghp_` is hidden, any
+// other secret only keeps its length
+fn mask(secret: &str, kind: Option<SecretKind>) -> String {
+    let prefix_len = match kind {
+        Some(SecretKind::AwsAccessKey) => 4,
+        Some(SecretKind::GithubToken) if secret.starts_with("github_pat_") => 11,
+        Some(SecretKind::GithubToken) => 4,
+        _ => 0,
+    };
+    let visible: String = secret.chars().take(prefix_len).collect();
+    let hidden = secret.chars().count() - visible.chars().count();
+
+    format!("{}{}", visible, "*".repeat(hidden))
+}
+
+fn char_boundary_at_or_before(text: &str, mut index: usize) -> usize {
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+fn char_boundary_at_or_after(text: &str, mut index: usize) -> usize {
+    while !text.is_char_boundary(index) {
+        index += 1;
+    }
+    index
+}
+
+/// Deduplicated tags describing the secrets in a clip
+pub fn secret_tags(matches: &[SecretMatch]) -> Vec<String> {
+    let mut tags = vec!["secret".to_string()];
+
+    for m in matches {
+        let tag = m.kind.tag().to_string();
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+
+    tags
+}
+
+fn looks_random(candidate: &str) -> bool {
+    let has_lower = candidate.chars().any(|c| c.is_ascii_lowercase());
+    let has_upper = candidate.chars().any(|c| c.is_ascii_uppercase());
+    let has_digit = candidate.chars().any(|c| c.is_ascii_digit());
+
+    // hex digests and plain words only use one or two character classes
+    has_lower && has_upper && has_digit && shannon_entropy(candidate) >= 4.0
+}
+
+/// Shannon entropy in bits per character
+fn shannon_entropy(s: &str) -> f64 {
+    let mut counts: HashMap<char, usize> = HashMap::new();
+    let mut total = 0usize;
+
+    for c in s.chars() {
+        *counts.entry(c).or_insert(0) += 1;
+        total += 1;
+    }
+
+    if total == 0 {
+        return 0.0;
+    }
+
+    counts
+        .values()
+        .map(|&count| {
+            let p = count as f64 / total as f64;
+            -p * p.log2()
+        })
+        .sum()
+}
@@ -1,3 +1,5 @@
+use crate::canonical::{self, CanonicalRules};
+use crate::AppState;
 use rusqlite::{params, Connection};
 use std::{
     collections::HashMap,
@@ -58,7 +60,83 @@ impl SettingsManager {
                 }
             }
         }
-        let defaults = vec![("global_hotkey", "CommandOrControl+Shift+S")];
+        let defaults = vec![
+            ("global_hotkey", "CommandOrControl+Shift+S"),
+            // category -> seconds until clips in it are deleted
+            ("expiry_rules", r#"{"credentials": 3600}"#),
+            // skip_ai | redact | block
+            ("secret_policy", "skip_ai"),
+            // swap emails, phones, IPs and these names for placeholders before LLM calls
+            ("pii_redaction", "true"),
+            ("pii_names", "[]"),
+            // openai | openai_compatible | anthropic | ollama, llm_model and llm_base_url
+            // fall back to the provider's defaults when unset
+            ("llm_provider", "openai"),
+            // per-attempt timeout (for streamed answers: until the first text and between chunks)
+            // and retries for transient failures (timeouts, 429, 5xx)
+            ("llm_timeout_secs", "30"),
+            ("llm_max_retries", "3"),
+            // pause AI after this many failed requests in a row, for the cooldown
+            ("llm_circuit_threshold", "5"),
+            ("llm_circuit_cooldown_secs", "300"),
+            // background workers processing the AI queue, read at startup
+            ("ai_workers", "2"),
+            // reuse LLM results for identical content for this long, up to this many entries
+            ("llm_cache_enabled", "true"),
+            ("llm_cache_ttl_days", "30"),
+            ("llm_cache_max_entries", "5000"),
+            // language titles and summaries are written in, the {{language}} prompt variable
+            ("llm_language", "English"),
+            // longest text sent in one request, in estimated tokens. longer text is categorized
+            // by its beginning and summarized in parts, up to llm_max_chunks of them
+            ("llm_chunk_tokens", "1500"),
+            ("llm_max_chunks", "20"),
+            // links and images are always summarized, other text: never | long | always.
+            // long means at least summarize_min_tokens
+            ("summarize_text", "never"),
+            ("summarize_min_tokens", "800"),
+            // how many past category/tag corrections are shown to the LLM as examples, 0 turns it off
+            ("llm_few_shot_examples", "3"),
+            // monthly spend in USD: warn at the soft limit, go offline at the hard one. empty is no limit
+            ("llm_budget_monthly_soft_usd", ""),
+            ("llm_budget_monthly_hard_usd", ""),
+            // model name prefix -> [input, output] USD per million tokens, on top of the built-in prices
+            ("llm_prices", "{}"),
+            // shown next to the seat id in usage reports, e.g. the user's name or team
+            ("seat_label", ""),
+            // fetch the page behind URL clips to summarize it, within these limits
+            ("fetch_pages", "true"),
+            ("fetch_timeout_secs", "15"),
+            ("fetch_max_bytes", "2000000"),
+            ("fetch_max_redirects", "5"),
+            // keep an offline copy of URL clips in these categories or on these domains
+            // (subdomains included), JSON arrays. with up to archive_max_images images each
+            ("archive_categories", "[]"),
+            ("archive_domains", "[]"),
+            ("archive_max_images", "5"),
+            // re-check stored links for dead or moved pages this often, 0 turns it off
+            ("link_check_interval_hours", "168"),
+            // query parameters (a trailing * matches a prefix) and subdomains dropped from URLs
+            // before comparing them, plus per-domain overrides. clips of the same page are merged
+            (
+                "url_strip_params",
+                r#"["utm_*", "fbclid", "gclid", "dclid", "msclkid", "yclid", "mc_cid", "mc_eid", "igshid", "ref", "ref_src", "ref_url", "_hsenc", "_hsmi"]"#,
+            ),
+            ("url_strip_subdomains", r#"["www", "m", "mobile"]"#),
+            (
+                "url_domain_rules",
+                r#"{"youtube.com": {"keep_params": ["v", "list"]}}"#,
+            ),
+            ("dedupe_urls", "true"),
+            // auto | openai | openai_compatible | ollama | none for semantic search. auto follows
+            // llm_provider, the model, base URL and key fall back to the provider's defaults
+            ("embedding_provider", "auto"),
+            ("embedding_model", ""),
+            ("embedding_base_url", ""),
+            ("embedding_api_key", ""),
+            // share of the meaning-based ranking in hybrid search, the rest is keyword matches
+            ("search_semantic_weight", "0.5"),
+        ];
 
         for (key, default_value) in defaults {
             if !settings.contains_key(key) {
@@ -71,6 +149,16 @@ impl SettingsManager {
                 println!("Set default for {}: {}", key, default_value);
             }
         }
+
+        // random id that tells this install's usage reports apart from other seats
+        if !settings.contains_key("seat_id") {
+            let seat_id = format!("{:016x}", rand::random::<u64>());
+            conn.execute(
+                "INSERT OR REPLACE INTO settings (key, value, updated_at) VALUES ('seat_id', ?, CURRENT_TIMESTAMP)",
+                params![seat_id],
+            )?;
+            settings.insert("seat_id".to_string(), seat_id);
+        }
         // release the lock
         drop(settings);
 
@@ -137,11 +225,22 @@ pub async fn set_setting(
     key: String,
     value: String,
     settings_manager: State<'_, SettingsManagerState>,
+    state: State<'_, AppState>,
 ) -> Result<(), String> {
     settings_manager
         .0
         .set_setting(&key, &value)
-        .map_err(|e| format!("Failed to set setting: {}", e))
+        .map_err(|e| format!("Failed to set setting: {}", e))?;
+
+    // stored canonical URLs follow the rules they were made with
+    if key.starts_with("url_") {
+        let rules = CanonicalRules::from_settings(&settings_manager.0);
+        let changed = canonical::refresh_canonical_urls(&state.db_path, &rules)
+            .map_err(|e| format!("Failed to update canonical URLs: {}", e))?;
+        println!("Updated {} canonical URL(s)", changed);
+    }
+
+    Ok(())
 }
 
 #[tauri::command]
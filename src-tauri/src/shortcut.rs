@@ -1,4 +1,9 @@
-use crate::llm;
+use crate::canonical::{self, CanonicalRules};
+use crate::expiry;
+use crate::llm::ClipAnalysis;
+use crate::queue;
+use crate::secrets::{self, SecretMatch, SecretPolicy};
+use crate::settings::SettingsManagerState;
 use arboard::{Clipboard, ImageData};
 use base64::{engine::general_purpose, Engine};
 use enigo::{
@@ -25,6 +30,23 @@ pub enum Clip {
     },
 }
 
+impl Clip {
+    /// Read a clip back from the JSON stored in the `clips.clip` column
+    pub fn from_stored(value: &serde_json::Value) -> Option<Clip> {
+        match value["type"].as_str()? {
+            "text" => Some(Clip::Text {
+                plain: value["content"].as_str().unwrap_or("").to_string(),
+            }),
+            "image" => Some(Clip::Image {
+                data: value["content"].as_str().unwrap_or("").to_string(),
+                width: value["width"].as_u64().unwrap_or(0) as usize,
+                height: value["height"].as_u64().unwrap_or(0) as usize,
+            }),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ClipContext {
     pub suggested_category: Option<String>,
@@ -56,56 +78,36 @@ pub fn handle_capture(app: &AppHandle) {
     if let Some(clip) = read_clipboard_with_retry(5, Duration::from_millis(50)) {
         let db_path = app.state::<crate::AppState>().db_path.clone();
 
+        // secrets are handled locally and never reach the LLM
+        let found_secrets = secrets::scan_clip(&clip);
+        if !found_secrets.is_empty() {
+            handle_secret_capture(app, db_path, clip, &found_secrets);
+            return;
+        }
+
         let app_handle = app.clone();
-        let clip_clone = clip.clone();
         tauri::async_runtime::spawn(async move {
-            // Get category and tags from LLM
-            let (category, tags) = match llm::get_llm_category(&clip_clone).await {
-                Ok(category_response) => (category_response.category, category_response.tags),
-                Err(e) => {
-                    eprintln!("LLM categorization failed: {}", e);
-                    ("other".to_string(), vec!["uncategorized".to_string()])
-                }
-            };
+            if let Some(clip_id) = merge_duplicate_url(&app_handle, &db_path, &clip) {
+                println!("Clip {} captured again, moved back to the top", clip_id);
+                return;
+            }
+
+            // saved right away with local guesses, the AI queue fills in the rest
+            let placeholder = ClipAnalysis::fallback(&clip, false);
 
-            let mut summary: String = String::new();
-
-            match &clip_clone {
-                Clip::Text { plain } => {
-                    if is_url(plain) {
-                        match llm::get_clip_summary(&clip_clone).await {
-                            Ok(suggested_summary) => summary = suggested_summary,
-                            Err(e) => {
-                                eprintln!("LLM summarization failed: {}", e);
-                                summary = "No summary available".to_string();
-                            }
-                        };
+            let clip_id =
+                match save_clip(&app_handle, &db_path, &clip, &placeholder, "pending").await {
+                    Ok(clip_id) => clip_id,
+                    Err(e) => {
+                        eprintln!("Failed to save clip: {}", e);
+                        return;
                     }
-                }
-                Clip::Image { .. } => {
-                    match llm::get_clip_summary(&clip_clone).await {
-                        Ok(suggested_summary) => summary = suggested_summary,
-                        Err(e) => {
-                            eprintln!("LLM summarization failed: {}", e);
-                            summary = "No summary available".to_string();
-                        }
-                    };
-                }
-            }
+                };
 
-            if let Err(e) = save_clip(
-                &app_handle,
-                &db_path,
-                &clip_clone,
-                &category,
-                &summary,
-                &tags,
-            )
-            .await
-            {
-                eprintln!("Failed to save clip: {}", e);
+            if let Err(e) = queue::enqueue_analysis(&app_handle, &db_path, clip_id) {
+                eprintln!("Failed to queue AI job for clip {}: {}", clip_id, e);
             } else {
-                println!("Clip saved to category: {} with tags: {:?}", category, tags);
+                println!("Clip {} saved, queued for AI", clip_id);
             }
         });
     } else {
@@ -113,6 +115,84 @@ pub fn handle_capture(app: &AppHandle) {
     }
 }
 
+/// A link to a page that's already saved doesn't make a new clip, the earlier one is moved
+/// back to the top instead. Returns its id
+fn merge_duplicate_url(app_handle: &AppHandle, db_path: &PathBuf, clip: &Clip) -> Option<i64> {
+    let settings = &app_handle.state::<SettingsManagerState>().0;
+    if settings.get_setting("dedupe_urls").as_deref() == Some("false") {
+        return None;
+    }
+    let canonical_url = match clip {
+        Clip::Text { plain } if is_url(plain) => {
+            CanonicalRules::from_settings(settings).canonicalize(plain)?
+        }
+        _ => return None,
+    };
+
+    let result = Connection::open(db_path).and_then(|conn| {
+        let Some(clip_id) = canonical::find_duplicate(&conn, &canonical_url)? else {
+            return Ok(None);
+        };
+        conn.execute(
+            "UPDATE clips SET created_at = CURRENT_TIMESTAMP WHERE id = ?",
+            params![clip_id],
+        )?;
+        Ok(Some(clip_id))
+    });
+
+    match result {
+        Ok(Some(clip_id)) => {
+            let _ = app_handle.emit("clip-updated", clip_id.to_string());
+            Some(clip_id)
+        }
+        Ok(None) => None,
+        Err(e) => {
+            eprintln!(
+                "Failed to look for an earlier copy of {}: {}",
+                canonical_url, e
+            );
+            None
+        }
+    }
+}
+
+fn handle_secret_capture(
+    app: &AppHandle,
+    db_path: PathBuf,
+    clip: Clip,
+    found_secrets: &[SecretMatch],
+) {
+    let policy = SecretPolicy::from_settings(&app.state::<SettingsManagerState>().0);
+    let tags = secrets::secret_tags(found_secrets);
+
+    let clip = match (policy, clip) {
+        (SecretPolicy::Block, _) => {
+            println!("[clipper] Capture blocked, clipboard contains {:?}", tags);
+            return;
+        }
+        (SecretPolicy::Redact, Clip::Text { plain }) => Clip::Text {
+            plain: secrets::redact_secrets(&plain, found_secrets),
+        },
+        (_, clip) => clip,
+    };
+
+    let app_handle = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let analysis = ClipAnalysis {
+            category: "credentials".to_string(),
+            tags: tags.clone(),
+            title: String::new(),
+            summary: String::new(),
+        };
+
+        if let Err(e) = save_clip(&app_handle, &db_path, &clip, &analysis, "skipped").await {
+            eprintln!("Failed to save clip: {}", e);
+        } else {
+            println!("Clip with secrets saved locally with tags: {:?}", tags);
+        }
+    });
+}
+
 pub fn is_url(text: &str) -> bool {
     match Url::parse(text) {
         Ok(url) => {
@@ -198,10 +278,16 @@ pub async fn save_clip(
     app_handle: &AppHandle,
     db_path: &PathBuf,
     clip: &Clip,
-    category: &str,
-    summary: &str,
-    tags: &[String],
-) -> Result<(), Box<dyn std::error::Error>> {
+    analysis: &ClipAnalysis,
+    ai_status: &str,
+) -> Result<i64, Box<dyn std::error::Error>> {
+    let ClipAnalysis {
+        category,
+        tags,
+        title,
+        summary,
+    } = analysis;
+
     let json_data = match clip {
         Clip::Text { plain } => {
             serde_json::json!({
@@ -232,16 +318,41 @@ pub async fn save_clip(
     // Convert tags to JSON string
     let tags_json = serde_json::to_string(tags)?;
 
+    let expires_in =
+        expiry::expiry_for_category(&app_handle.state::<SettingsManagerState>().0, category);
+
+    // an empty title means none was given, the frontend then falls back to the content
+    let title = Some(title.as_str()).filter(|title| !title.is_empty());
+
+    // kept next to the original so later links to the same page are recognized
+    let canonical_url = match clip {
+        Clip::Text { plain } if is_url(plain) => {
+            CanonicalRules::from_settings(&app_handle.state::<SettingsManagerState>().0)
+                .canonicalize(plain)
+        }
+        _ => None,
+    };
+
     let conn = Connection::open(db_path)?;
 
     conn.execute(
-        "INSERT INTO clips(clip, category, summary, tags) VALUES (?,?,?,?)",
-        params![json_data.to_string(), category, summary, tags_json],
+        "INSERT INTO clips(clip, category, title, summary, tags, expires_at, ai_status, canonical_url) VALUES (?,?,?,?,?, datetime('now', ?), ?, ?)",
+        params![
+            json_data.to_string(),
+            category,
+            title,
+            summary,
+            tags_json,
+            expiry::expiry_modifier(expires_in),
+            ai_status,
+            canonical_url
+        ],
     )?;
+    let clip_id = conn.last_insert_rowid();
 
     app_handle.emit("clip-saved", {}).unwrap();
 
-    Ok(())
+    Ok(clip_id)
 }
 
 pub fn parse_hotkey_string(